@@ -1,5 +1,4 @@
-use cli_toolbox::{System, Program, TermColor, ShellCommand};
-#[macro_use] extern crate text_io;
+use cli_toolbox::{System, Program, TermColor, ShellCommand, CliError};
 
 fn main() {
     // Create a system with custom settings
@@ -14,13 +13,13 @@ fn main() {
         .use_defaults()
         .description("Validate numeric input")
         .tag("example")
-        .action(|| {
-            println!("Enter a number to validate:");
-            let input: String = read!("{}\n");
+        .action_with_context(|ctx| {
+            let input = ctx.input("Enter a number to validate:")?;
             match input.trim().parse::<i32>() {
-                Ok(num) => println!("Valid number: {}", num),
-                Err(_) => println!("Invalid number! Please enter a valid integer."),
+                Ok(num) => ctx.print(&format!("Valid number: {}", num)),
+                Err(_) => ctx.err("Invalid number! Please enter a valid integer."),
             }
+            Ok(())
         })
         .build();
 
@@ -28,24 +27,23 @@ fn main() {
         .use_defaults()
         .description("Simple calculator (demonstrates error handling)")
         .tag("example")
-        .action(|| {
-            println!("Enter first number:");
-            let num1: String = read!("{}\n");
-            println!("Enter second number:");
-            let num2: String = read!("{}\n");
-            
+        .action_with_context(|ctx| {
+            let num1 = ctx.input("Enter first number:")?;
+            let num2 = ctx.input("Enter second number:")?;
+
             match (num1.trim().parse::<i32>(), num2.trim().parse::<i32>()) {
                 (Ok(n1), Ok(n2)) => {
-                    println!("Sum: {}", n1 + n2);
-                    println!("Difference: {}", n1 - n2);
-                    println!("Product: {}", n1 * n2);
+                    ctx.print(&format!("Sum: {}", n1 + n2));
+                    ctx.print(&format!("Difference: {}", n1 - n2));
+                    ctx.print(&format!("Product: {}", n1 * n2));
                     if n2 != 0 {
-                        println!("Quotient: {}", n1 as f32 / n2 as f32);
+                        ctx.print(&format!("Quotient: {}", n1 as f32 / n2 as f32));
+                        Ok(())
                     } else {
-                        println!("Cannot divide by zero!");
+                        Err(CliError::InvalidInput("Cannot divide by zero!".to_string()))
                     }
                 },
-                _ => println!("Invalid input! Please enter valid integers."),
+                _ => Err(CliError::InvalidInput("Please enter valid integers.".to_string())),
             }
         })
         .build();
@@ -66,15 +64,14 @@ fn main() {
         .use_defaults()
         .description("Demonstrates dynamic shell command arguments with validation")
         .tag("example")
-        .action(|| {
-            println!("Enter command to execute:");
-            let cmd: String = read!("{}\n");
+        .action_with_context(|ctx| {
+            let cmd = ctx.input("Enter command to execute:")?;
             let mut command = ShellCommand::new(cmd.trim());
             
             // Add arguments dynamically based on user input
-            println!("Enter arguments (one per line, empty line to finish):");
+            ctx.print("Enter arguments (one per line, empty line to finish):");
             loop {
-                let input: String = read!("{}\n");
+                let input = ctx.input("Argument:")?;
                 if input.trim().is_empty() {
                     break;
                 }
@@ -85,13 +82,14 @@ fn main() {
             match command.execute() {
                 Ok(status) => {
                     if status.success() {
-                        println!("Command executed successfully!");
+                        ctx.print("Command executed successfully!");
                     } else {
-                        println!("Command failed with status: {}", status);
+                        ctx.err(&format!("Command failed with status: {}", status));
                     }
                 }
-                Err(e) => println!("Command error: {:?}", e),
+                Err(e) => ctx.err(&format!("Command error: {:?}", e)),
             }
+            Ok(())
         })
         .build();
 
@@ -100,16 +98,15 @@ fn main() {
         .use_defaults()
         .description("Demonstrates shell command without validation")
         .tag("example")
-        .action(|| {
-            println!("Enter command to execute (no validation):");
-            let cmd: String = read!("{}\n");
+        .action_with_context(|ctx| {
+            let cmd = ctx.input("Enter command to execute (no validation):")?;
             let mut command = ShellCommand::new(cmd.trim())
                 .with_validation(false);
             
             // Add arguments dynamically based on user input
-            println!("Enter arguments (one per line, empty line to finish):");
+            ctx.print("Enter arguments (one per line, empty line to finish):");
             loop {
-                let input: String = read!("{}\n");
+                let input = ctx.input("Argument:")?;
                 if input.trim().is_empty() {
                     break;
                 }
@@ -120,13 +117,14 @@ fn main() {
             match command.execute() {
                 Ok(status) => {
                     if status.success() {
-                        println!("Command executed successfully!");
+                        ctx.print("Command executed successfully!");
                     } else {
-                        println!("Command failed with status: {}", status);
+                        ctx.err(&format!("Command failed with status: {}", status));
                     }
                 }
-                Err(e) => println!("Command error: {:?}", e),
+                Err(e) => ctx.err(&format!("Command error: {:?}", e)),
             }
+            Ok(())
         })
         .build();
 
@@ -143,7 +141,9 @@ fn main() {
     // Show programs with a specific tag
     system.display();
     system.menu();
-    system.menu_with_tags_filter(vec!["demo".to_string()]);
+    if let Err(e) = system.menu_with_tags_filter("demo & !slow") {
+        println!("{}", e);
+    }
 
     // Run the main menu
 } 
\ No newline at end of file
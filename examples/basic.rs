@@ -39,6 +39,6 @@ fn main() {
     // Show help
     system.show_help();
 
-    // Run the menu
-    system.menu();
+    // Run the menu until the user quits
+    system.run();
 } 
\ No newline at end of file
@@ -0,0 +1,291 @@
+//--------------------- Args --------------------------------------------------
+// path src\cli\args.rs
+// Declared arguments and flags for programs.
+// A program that declares any has its arguments parsed and checked before it runs,
+// whether they come from the command line or are typed after its name at the menu prompt.
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::cli::system::CliError;
+
+/// The type an argument's value must parse as.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ArgType {
+    String,
+    Int,
+    Float,
+    Bool,
+}
+
+impl fmt::Display for ArgType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let type_str = match self {
+            ArgType::String => "string",
+            ArgType::Int => "integer",
+            ArgType::Float => "number",
+            ArgType::Bool => "true/false",
+        };
+        write!(f, "{}", type_str)
+    }
+}
+
+impl ArgType {
+    fn check(&self, value: &str) -> bool {
+        match self {
+            ArgType::String => true,
+            ArgType::Int => value.parse::<i64>().is_ok(),
+            ArgType::Float => value.parse::<f64>().is_ok(),
+            ArgType::Bool => value.parse::<bool>().is_ok(),
+        }
+    }
+}
+
+/// A value a program takes, given positionally in declaration order or as `--name value`.
+#[derive(Clone, Debug)]
+pub struct Arg {
+    name: String,
+    kind: ArgType,
+    default: Option<String>,
+    required: bool,
+    help: String,
+}
+
+impl Arg {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            kind: ArgType::String,
+            default: None,
+            required: false,
+            help: String::new(),
+        }
+    }
+
+    pub fn kind(mut self, kind: ArgType) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    pub fn default(mut self, value: impl Into<String>) -> Self {
+        self.default = Some(value.into());
+        self
+    }
+
+    pub fn required(mut self, required: bool) -> Self {
+        self.required = required;
+        self
+    }
+
+    pub fn help(mut self, help: impl Into<String>) -> Self {
+        self.help = help.into();
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_kind(&self) -> ArgType {
+        self.kind
+    }
+
+    pub fn get_default(&self) -> Option<&str> {
+        self.default.as_deref()
+    }
+
+    pub fn is_required(&self) -> bool {
+        self.required
+    }
+
+    pub fn get_help(&self) -> &str {
+        &self.help
+    }
+
+    /// How the argument shows up in a usage line, `<name>` when required and `[name]` otherwise.
+    pub fn usage(&self) -> String {
+        if self.required {
+            format!("<{}>", self.name)
+        } else {
+            format!("[{}]", self.name)
+        }
+    }
+}
+
+/// An on/off switch, given as `--name` or its short form `-n`.
+#[derive(Clone, Debug)]
+pub struct Flag {
+    name: String,
+    short: Option<char>,
+    help: String,
+}
+
+impl Flag {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            short: None,
+            help: String::new(),
+        }
+    }
+
+    pub fn short(mut self, short: char) -> Self {
+        self.short = Some(short);
+        self
+    }
+
+    pub fn help(mut self, help: impl Into<String>) -> Self {
+        self.help = help.into();
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_short(&self) -> Option<char> {
+        self.short
+    }
+
+    pub fn get_help(&self) -> &str {
+        &self.help
+    }
+
+    pub fn usage(&self) -> String {
+        match self.short {
+            Some(c) => format!("[-{}|--{}]", c, self.name),
+            None => format!("[--{}]", self.name),
+        }
+    }
+}
+
+/// Argument values and flags after parsing, with defaults filled in.
+#[derive(Clone, Debug, Default)]
+pub struct ParsedArgs {
+    values: HashMap<String, String>,
+    flags: HashSet<String>,
+}
+
+impl ParsedArgs {
+    /// The raw value of an argument, if it was given or has a default.
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(String::as_str)
+    }
+
+    /// The value of an argument parsed as `T`.
+    pub fn get<T: FromStr>(&self, name: &str) -> Option<T> {
+        self.value(name).and_then(|v| v.parse().ok())
+    }
+
+    pub fn flag(&self, name: &str) -> bool {
+        self.flags.contains(name)
+    }
+
+    /// Parses `args` against the declarations, checking types, required arguments and unknown flags.
+    pub fn parse(declared: &[Arg], flags: &[Flag], args: &[String]) -> Result<Self, CliError> {
+        let mut parsed = ParsedArgs::default();
+        let mut positional = declared.iter();
+        let mut only_positional = false;
+        let mut tokens = args.iter();
+
+        while let Some(token) = tokens.next() {
+            if only_positional || !token.starts_with('-') || token == "-" || token.parse::<f64>().is_ok() {
+                let arg = positional.by_ref().find(|a| !parsed.values.contains_key(&a.name))
+                    .ok_or_else(|| CliError::InvalidInput(format!("Unexpected argument '{}'", token)))?;
+                parsed.set(arg, token)?;
+            } else if token == "--" {
+                only_positional = true;
+            } else if let Some(long) = token.strip_prefix("--") {
+                let (name, inline) = match long.split_once('=') {
+                    Some((name, value)) => (name, Some(value.to_string())),
+                    None => (long, None),
+                };
+                if let Some(flag) = flags.iter().find(|f| f.name == name) {
+                    // `--loud=false` turns the flag off rather than dropping the value
+                    let on = match inline {
+                        Some(value) => value.parse::<bool>().map_err(|_| CliError::InvalidInput(
+                            format!("--{} is a flag, expected true or false but got '{}'", name, value)))?,
+                        None => true,
+                    };
+                    if on {
+                        parsed.flags.insert(flag.name.clone());
+                    } else {
+                        parsed.flags.remove(&flag.name);
+                    }
+                } else if let Some(arg) = declared.iter().find(|a| a.name == name) {
+                    let value = match inline {
+                        Some(value) => value,
+                        None => tokens.next().cloned()
+                            .ok_or_else(|| CliError::InvalidInput(format!("--{} expects a value", name)))?,
+                    };
+                    parsed.set(arg, &value)?;
+                } else {
+                    return Err(CliError::InvalidInput(format!("Unknown flag '--{}'", name)));
+                }
+            } else {
+                for c in token[1..].chars() {
+                    let flag = flags.iter().find(|f| f.short == Some(c))
+                        .ok_or_else(|| CliError::InvalidInput(format!("Unknown flag '-{}'", c)))?;
+                    parsed.flags.insert(flag.name.clone());
+                }
+            }
+        }
+
+        for arg in declared {
+            if parsed.values.contains_key(&arg.name) {
+                continue;
+            }
+            match &arg.default {
+                Some(default) => {
+                    parsed.values.insert(arg.name.clone(), default.clone());
+                }
+                None if arg.required => {
+                    return Err(CliError::InvalidInput(format!("Missing argument {}", arg.usage())));
+                }
+                None => {}
+            }
+        }
+        Ok(parsed)
+    }
+
+    fn set(&mut self, arg: &Arg, value: &str) -> Result<(), CliError> {
+        if !arg.kind.check(value) {
+            return Err(CliError::InvalidInput(format!("{} expects {}, got '{}'", arg.name, arg.kind, value)));
+        }
+        self.values.insert(arg.name.clone(), value.to_string());
+        Ok(())
+    }
+}
+
+/// Splits a line typed at a prompt into arguments, keeping quoted text together.
+pub fn split_args(line: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut quote: Option<char> = None;
+    let mut in_arg = false;
+
+    for c in line.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => current.push(c),
+            None if c == '"' || c == '\'' => {
+                quote = Some(c);
+                in_arg = true;
+            }
+            None if c.is_whitespace() => {
+                if in_arg {
+                    args.push(std::mem::take(&mut current));
+                    in_arg = false;
+                }
+            }
+            None => {
+                current.push(c);
+                in_arg = true;
+            }
+        }
+    }
+    if in_arg {
+        args.push(current);
+    }
+    args
+}
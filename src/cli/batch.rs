@@ -0,0 +1,95 @@
+//--------------------- Batch --------------------------------------------------
+// path src\cli\batch.rs
+// Running several programs in one go, picked at the menu prompt with lists, ranges or a tag,
+// e.g. `1,3,5-7` or `#nightly`, or `&` in front to run them in parallel.
+// Each run is timed and a summary table is printed at the end.
+use std::time::Duration;
+
+use console::Style;
+
+use crate::cli::system::CliError;
+
+/// How one program in a batch went.
+#[derive(Debug)]
+pub enum BatchStatus {
+    Passed,
+    Failed(CliError),
+    /// Not run, because an earlier program failed and the system stops on failure, or because it's disabled
+    /// or wasn't confirmed.
+    Skipped,
+}
+
+/// One row of a batch summary.
+#[derive(Debug)]
+pub struct BatchResult {
+    pub index: usize,
+    pub name: String,
+    pub status: BatchStatus,
+    pub duration: Duration,
+}
+
+impl BatchResult {
+    pub fn is_failed(&self) -> bool {
+        matches!(self.status, BatchStatus::Failed(_))
+    }
+}
+
+/// Whether a line typed at the menu prompt picks several programs rather than one.
+/// Spaces are only allowed around the commas and in tag queries, so `greet a,b` still runs `greet` with an argument.
+pub(crate) fn is_batch(input: &str) -> bool {
+    let items: Vec<&str> = input.split(',').map(str::trim).collect();
+    if items.iter().any(|item| !item.starts_with('#') && item.contains(char::is_whitespace)) {
+        return false;
+    }
+    items.len() > 1 || items[0].starts_with('#') || parse_range(items[0]).is_some()
+}
+
+/// Parses `5-7` into its inclusive bounds.
+pub(crate) fn parse_range(item: &str) -> Option<(usize, usize)> {
+    let (start, end) = item.split_once('-')?;
+    Some((start.trim().parse().ok()?, end.trim().parse().ok()?))
+}
+
+fn status_label(status: &BatchStatus) -> (&'static str, Style) {
+    match status {
+        BatchStatus::Passed => ("ok", Style::new().green()),
+        BatchStatus::Failed(_) => ("failed", Style::new().red()),
+        BatchStatus::Skipped => ("skipped", Style::new().yellow()),
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    format!("{:.2} ms", duration.as_micros() as f64 / 1000.0)
+}
+
+/// The summary table, one line per program followed by the totals.
+pub(crate) fn summary_lines(results: &[BatchResult]) -> Vec<String> {
+    let mut lines = vec![format!("{0: <30} {1: <8} {2}", "Program", "Status", "Time")];
+    for result in results {
+        let (status, style) = status_label(&result.status);
+        let time = match result.status {
+            BatchStatus::Skipped => "-".to_string(),
+            _ => format_duration(result.duration),
+        };
+        // Pad before styling so the escape codes don't throw the columns off
+        lines.push(format!("{0: <30} {1} {2}", result.name, style.apply_to(format!("{: <8}", status)), time));
+    }
+
+    let failed = results.iter().filter(|r| r.is_failed()).count();
+    let skipped = results.iter().filter(|r| matches!(r.status, BatchStatus::Skipped)).count();
+    lines.push(format!("{} run, {} failed, {} skipped", results.len() - skipped, failed, skipped));
+    lines
+}
+
+/// A finished parallel run: a header in the program's style, or red if it failed, then what it printed.
+pub(crate) fn block_lines(result: &BatchResult, style: &Style, output: &[String]) -> Vec<String> {
+    let (status, status_style) = status_label(&result.status);
+    let header_style = if result.is_failed() { status_style } else { style.clone() };
+    let mut lines = vec![format!("{}", header_style.apply_to(
+        format!("── {} ({}, {}) ──", result.name, status, format_duration(result.duration))))];
+    lines.extend(output.iter().map(|line| format!("  {}", line)));
+    if let BatchStatus::Failed(e) = &result.status {
+        lines.push(format!("  {}", Style::new().red().apply_to(e)));
+    }
+    lines
+}
@@ -0,0 +1,95 @@
+//--------------------- Commands --------------------------------------------------
+// path src\cli\commands.rs
+// Meta-commands, the words the menu prompt understands besides picking a program.
+// Every system starts with the built-ins and more can be registered with their own handler and help text.
+// The prompt checks these before falling back to program selection.
+use crate::cli::system::{CliError, System};
+
+/// Handles a registered command, given the system it was typed in and the words after its name.
+pub type CommandHandler = Box<dyn FnMut(&mut System, &[String]) -> Result<(), CliError>>;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Builtin {
+    Help,
+    Back,
+    Home,
+    Clear,
+    History,
+    Settings,
+    Bench,
+    Doctor,
+    Quit,
+}
+
+pub(crate) enum CommandAction {
+    Builtin(Builtin),
+    Custom(CommandHandler),
+}
+
+pub struct MetaCommand {
+    name: String,
+    usage: String,
+    help: String,
+    pub(crate) action: CommandAction,
+}
+
+impl MetaCommand {
+    pub fn new<F>(name: impl Into<String>, help: impl Into<String>, handler: F) -> Self
+    where
+        F: FnMut(&mut System, &[String]) -> Result<(), CliError> + 'static,
+    {
+        let name = name.into();
+        Self {
+            usage: name.clone(),
+            name,
+            help: help.into(),
+            action: CommandAction::Custom(Box::new(handler)),
+        }
+    }
+
+    fn builtin(usage: &str, help: &str, builtin: Builtin) -> Self {
+        Self {
+            name: usage.split_whitespace().next().unwrap_or(usage).to_string(),
+            usage: usage.to_string(),
+            help: help.to_string(),
+            action: CommandAction::Builtin(builtin),
+        }
+    }
+
+    /// How the command is shown in help, e.g. `deploy [env]`.
+    pub fn usage(mut self, usage: impl Into<String>) -> Self {
+        self.usage = usage.into();
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_usage(&self) -> &str {
+        &self.usage
+    }
+
+    pub fn get_help(&self) -> &str {
+        &self.help
+    }
+
+    pub fn is_builtin(&self) -> bool {
+        matches!(self.action, CommandAction::Builtin(_))
+    }
+}
+
+/// The commands every system starts with.
+pub(crate) fn builtin_commands() -> Vec<MetaCommand> {
+    vec![
+        MetaCommand::builtin("help [program]", "Show this help, or everything about one program", Builtin::Help),
+        MetaCommand::builtin("back", "Go up one level", Builtin::Back),
+        MetaCommand::builtin("home", "Go back to the top level", Builtin::Home),
+        MetaCommand::builtin("clear", "Clear the screen", Builtin::Clear),
+        MetaCommand::builtin("history", "List what has been typed at this prompt", Builtin::History),
+        MetaCommand::builtin("settings [key value]", "Show the settings, or change color, sleep or silent", Builtin::Settings),
+        MetaCommand::builtin("bench", "Run and time every program", Builtin::Bench),
+        MetaCommand::builtin("doctor", "Check every program's requirements", Builtin::Doctor),
+        MetaCommand::builtin("quit", "Leave the menu", Builtin::Quit),
+    ]
+}
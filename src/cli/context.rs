@@ -0,0 +1,149 @@
+//--------------------- Context --------------------------------------------------
+// path src\cli\context.rs
+// What a program's action gets to see while it runs.
+// The context reads and writes through the owning system's input and output,
+// and exposes the running program's metadata and the arguments it was started with.
+use std::{thread, time};
+use console::Style;
+
+use crate::cli::args::ParsedArgs;
+use crate::cli::prompt::*;
+use crate::cli::input::*;
+use crate::cli::output::*;
+use crate::cli::validate::Validator;
+use crate::cli::system::CliError;
+
+pub struct Context<'a> {
+    pub(crate) name: &'a str,
+    pub(crate) description: &'a str,
+    pub(crate) tags: &'a [String],
+    pub(crate) style: &'a Style,
+    pub(crate) sleep: u64,
+    pub(crate) args: &'a [String],
+    pub(crate) parsed: &'a ParsedArgs,
+    pub(crate) input: &'a SharedInput,
+    pub(crate) output: &'a SharedOutput,
+}
+
+impl<'a> Context<'a> {
+    /// Name of the running program.
+    pub fn name(&self) -> &str {
+        self.name
+    }
+
+    pub fn description(&self) -> &str {
+        self.description
+    }
+
+    pub fn tags(&self) -> &[String] {
+        self.tags
+    }
+
+    pub fn style(&self) -> &Style {
+        self.style
+    }
+
+    /// The arguments the program was started with, not including its name.
+    pub fn args(&self) -> &[String] {
+        self.args
+    }
+
+    pub fn arg(&self, index: usize) -> Option<&str> {
+        self.args.get(index).map(String::as_str)
+    }
+
+    /// The declared arguments and flags, parsed and checked before the program started.
+    pub fn parsed(&self) -> &ParsedArgs {
+        self.parsed
+    }
+
+    /// The value of a declared argument parsed as `T`, or its default when it wasn't given.
+    pub fn get<T: std::str::FromStr>(&self, name: &str) -> Option<T> {
+        self.parsed.get(name)
+    }
+
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.parsed.value(name)
+    }
+
+    pub fn flag(&self, name: &str) -> bool {
+        self.parsed.flag(name)
+    }
+
+    fn write_line(&self, line: impl std::fmt::Display) {
+        self.output.lock().unwrap().write_line(&line.to_string());
+    }
+
+    /// Prints in the program's style, the same way `Program::print` does.
+    pub fn print(&self, message: &str) {
+        self.write_line(self.style.apply_to(format!("{}> {}", self.name, message)));
+        thread::sleep(time::Duration::from_millis(self.sleep));
+    }
+
+    pub fn err(&self, message: &str) {
+        self.write_line(Style::new().red().apply_to(format!("{} Error> {}", self.name, message)));
+    }
+
+    /// Prompts through the system's input source. Like `System::input`, typing `quit` is a `CliError::Quit`,
+    /// return it with `?` and the system tears down and leaves.
+    pub fn input(&self, label: &str) -> Result<String, CliError> {
+        self.prompter().input(label)
+    }
+
+    /// Prompts for a password or token, like `System::input_secret`.
+    pub fn input_secret(&self, label: &str) -> Result<Secret, CliError> {
+        self.prompter().input_secret(label)
+    }
+
+    /// Prompts until `validator` accepts the answer, showing why it didn't each time.
+    pub fn input_with_validation(&self, label: &str, validator: impl Validator) -> Result<String, CliError> {
+        self.prompter().input_with_validation(label, validator)
+    }
+
+    /// Asks a yes or no question, like `System::confirm`.
+    pub fn confirm(&self, label: &str, default: bool) -> Result<bool, CliError> {
+        self.prompter().confirm(label, default)
+    }
+
+    /// Asks for one of the items by number or name, like `System::select`.
+    pub fn select<T: std::fmt::Display>(&self, label: &str, items: &[T]) -> Result<usize, CliError> {
+        self.prompter().select(label, items)
+    }
+
+    /// Asks for any number of the items, like `System::multi_select`.
+    pub fn multi_select<T: std::fmt::Display>(&self, label: &str, items: &[T]) -> Result<Vec<usize>, CliError> {
+        self.prompter().multi_select(label, items)
+    }
+
+    /// Prompts until the answer parses as `T`, like `System::input_parsed`.
+    pub fn input_parsed<T>(&self, label: &str) -> Result<T, CliError>
+    where
+        T: std::str::FromStr,
+        T::Err: std::fmt::Display,
+    {
+        self.prompt(Prompt::new(label))
+    }
+
+    /// Asks a typed question with a default, range or retry limit, see `Prompt`.
+    pub fn prompt<T>(&self, prompt: Prompt<T>) -> Result<T, CliError>
+    where
+        T: std::str::FromStr,
+        T::Err: std::fmt::Display,
+    {
+        self.prompter().prompt(prompt)
+    }
+
+    pub fn input_required(&self, label: &str) -> Result<String, CliError> {
+        self.prompter().input_required(label)
+    }
+
+    fn prompter(&self) -> Prompter<'_> {
+        Prompter {
+            name: self.name,
+            style: self.style,
+            input: self.input,
+            output: self.output,
+            error_label: format!("{} Error> ", self.name),
+        }
+    }
+}
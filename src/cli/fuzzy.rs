@@ -0,0 +1,85 @@
+//--------------------- Fuzzy --------------------------------------------------
+// path src\cli\fuzzy.rs
+// Loose matching for the menu prompt, used by `/` filtering and the "did you mean" suggestions.
+
+/// Scores how well `query` matches `candidate`, ignoring case, or `None` when the query's characters
+/// don't all appear in order. Consecutive characters and characters at the start of a word score higher,
+/// and shorter candidates win ties.
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<i64> {
+    let query: Vec<char> = query.to_lowercase().chars().collect();
+    let candidate: Vec<char> = candidate.to_lowercase().chars().collect();
+    if query.is_empty() {
+        return Some(0);
+    }
+
+    let mut score = 0;
+    let mut matched = 0;
+    let mut previous: Option<usize> = None;
+    for (i, c) in candidate.iter().enumerate() {
+        if matched == query.len() {
+            break;
+        }
+        if *c != query[matched] {
+            continue;
+        }
+        score += 10;
+        if previous.is_some_and(|p| p + 1 == i) {
+            score += 15;
+        }
+        if i == 0 || !candidate[i - 1].is_alphanumeric() {
+            score += 10;
+        }
+        previous = Some(i);
+        matched += 1;
+    }
+
+    if matched == query.len() {
+        Some(score - candidate.len() as i64)
+    } else {
+        None
+    }
+}
+
+/// The number of single character edits to turn `a` into `b`, ignoring case.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.to_lowercase().chars().collect();
+    let b: Vec<char> = b.to_lowercase().chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Ranks `candidates` by how close they are to a mistyped `word`, closest first.
+/// Close spellings and fuzzy matches are kept, anything else is dropped.
+pub fn suggestions<'a>(word: &str, candidates: impl IntoIterator<Item = &'a str>, limit: usize) -> Vec<String> {
+    let max_distance = (word.chars().count() / 3).max(2);
+    let mut ranked: Vec<(usize, i64, &str)> = candidates.into_iter()
+        .filter_map(|c| {
+            let distance = edit_distance(word, c);
+            let score = fuzzy_score(word, c);
+            if distance <= max_distance || score.is_some() {
+                Some((distance, -score.unwrap_or(i64::MIN / 2), c))
+            } else {
+                None
+            }
+        })
+        .collect();
+    ranked.sort();
+
+    let mut names: Vec<String> = Vec::new();
+    for (_, _, name) in ranked {
+        if !names.iter().any(|n| n == name) {
+            names.push(name.to_string());
+        }
+    }
+    names.truncate(limit);
+    names
+}
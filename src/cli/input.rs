@@ -0,0 +1,151 @@
+//--------------------- Input --------------------------------------------------
+// path src\cli\input.rs
+// Where the system reads the lines typed at its prompts.
+// Anything that prompts goes through an `InputSource`, so it can be scripted in tests
+// or replayed from a file instead of always reading stdin.
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, IsTerminal};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::cli::system::CliError;
+
+/// A source of input lines. Running out of lines is reported as `CliError::EndOfInput`.
+pub trait InputSource {
+    /// Reads the next line, without the line ending.
+    fn read_line(&mut self) -> Result<String, CliError>;
+
+    /// Whether a person is typing at a terminal, which allows the arrow-key menu.
+    fn is_terminal(&self) -> bool {
+        false
+    }
+
+    /// Reads a line that shouldn't be shown as it's typed, like a password. Sources without a terminal read it like any line.
+    fn read_secret(&mut self) -> Result<String, CliError> {
+        self.read_line()
+    }
+}
+
+/// An input source shared between a system and everything that prompts on its behalf.
+pub type SharedInput = Arc<Mutex<Box<dyn InputSource + Send>>>;
+
+pub fn shared_input(source: impl InputSource + Send + 'static) -> SharedInput {
+    Arc::new(Mutex::new(Box::new(source)))
+}
+
+fn trim_line_ending(mut line: String) -> String {
+    while line.ends_with('\n') || line.ends_with('\r') {
+        line.pop();
+    }
+    line
+}
+
+fn read_from(reader: &mut impl BufRead) -> Result<String, CliError> {
+    let mut line = String::new();
+    match reader.read_line(&mut line) {
+        Ok(0) => Err(CliError::EndOfInput),
+        Ok(_) => Ok(trim_line_ending(line)),
+        Err(e) => Err(CliError::Io(e.to_string())),
+    }
+}
+
+/// Reads from the process' standard input, the default for every system.
+pub struct StdinInput;
+
+impl InputSource for StdinInput {
+    fn read_line(&mut self) -> Result<String, CliError> {
+        read_from(&mut io::stdin().lock())
+    }
+
+    fn is_terminal(&self) -> bool {
+        io::stdin().is_terminal()
+    }
+
+    fn read_secret(&mut self) -> Result<String, CliError> {
+        // `read_secure_line` gives back nothing on a term that isn't a tty, so with stdout piped
+        // (`mytool | tee session.log`) it's read through stderr instead
+        let term = [console::Term::stdout(), console::Term::stderr()].into_iter().find(|term| term.is_term());
+        match term {
+            Some(term) if self.is_terminal() => term.read_secure_line().map_err(|e| CliError::Io(e.to_string())),
+            _ => self.read_line(),
+        }
+    }
+}
+
+/// An in-memory queue of lines, handy for tests and scripted sessions.
+pub struct ScriptedInput {
+    lines: VecDeque<String>,
+}
+
+impl ScriptedInput {
+    pub fn new<I, S>(lines: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            lines: lines.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    pub fn push(&mut self, line: impl Into<String>) {
+        self.lines.push_back(line.into());
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.lines.len()
+    }
+}
+
+impl InputSource for ScriptedInput {
+    fn read_line(&mut self) -> Result<String, CliError> {
+        self.lines.pop_front().ok_or(CliError::EndOfInput)
+    }
+}
+
+/// A value read with `input_secret`. It shows up as `********` when printed or debugged,
+/// `expose` gives the actual value.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(value: impl Into<String>) -> Self {
+        Self(value.into())
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", crate::cli::output::REDACTED)
+    }
+}
+
+impl std::fmt::Debug for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Secret({})", crate::cli::output::REDACTED)
+    }
+}
+
+/// Replays the lines of a file, one per prompt.
+pub struct FileInput {
+    reader: BufReader<File>,
+}
+
+impl FileInput {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self {
+            reader: BufReader::new(File::open(path)?),
+        })
+    }
+}
+
+impl InputSource for FileInput {
+    fn read_line(&mut self) -> Result<String, CliError> {
+        read_from(&mut self.reader)
+    }
+}
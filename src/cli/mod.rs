@@ -6,7 +6,18 @@
 //! 
 //! - [`System`]: The main CLI manager that handles program organization and user interaction
 //! - [`Program`]: Individual commands or sub-programs that can be executed
+//! - [`Runnable`]: What a program runs, implement it to keep state between runs
 //! - [`ShellCommand`]: Safe cross-platform shell command execution
+//! - [`Arg`] and [`Flag`]: Typed arguments a program declares, parsed from argv or the menu prompt
+//! - [`TagExpr`]: Tag queries like `db & !slow`, for filtering the menu and picking programs to run
+//! - [`Requirement`]: Tools, environment variables and files a program needs, checked by the `doctor` command
+//! - [`BatchResult`]: How each program went when several are run from one prompt line
+//! - [`MetaCommand`]: Words the menu prompt understands besides program names, built-in or registered
+//! - [`Context`]: What a program's action sees while it runs, its arguments, metadata and the system's input and output
+//! - [`Prompt`]: A typed question with a default, an allowed range and a retry limit
+//! - [`Validator`]: Checks for free-text answers that say why one isn't accepted, see [`validate`] for the built-in ones
+//! - [`InputSource`]: Where prompts read their lines from (stdin, a scripted queue or a file)
+//! - [`OutputSink`]: Where the system and its programs write to (terminal, a buffer, a file or nowhere)
 //! 
 //! # Error Handling
 //! 
@@ -14,6 +25,7 @@
 //! - Invalid input
 //! - Command failures
 //! - Program not found errors
+//! - Running out of input
 //! 
 //! # Example
 //! 
@@ -35,6 +47,28 @@
 
 pub mod system;
 pub mod program;
+pub mod input;
+pub mod output;
+pub mod context;
+pub mod args;
+pub mod commands;
+pub mod fuzzy;
+pub mod batch;
+pub mod tags;
+pub mod requirements;
+pub mod prompt;
+pub mod validate;
+pub(crate) mod picker;
 
 pub use system::System;
-pub use program::Program; 
\ No newline at end of file
+pub use program::{Program, Runnable};
+pub use context::Context;
+pub use args::{Arg, ArgType, Flag, ParsedArgs};
+pub use commands::MetaCommand;
+pub use batch::{BatchResult, BatchStatus};
+pub use tags::{TagExpr, TagNode};
+pub use requirements::Requirement;
+pub use prompt::Prompt;
+pub use validate::Validator;
+pub use input::{InputSource, ScriptedInput, FileInput, StdinInput, Secret};
+pub use output::{OutputSink, BufferOutput, FileOutput, NullOutput, TerminalOutput}; 
\ No newline at end of file
@@ -0,0 +1,227 @@
+//--------------------- Output --------------------------------------------------
+// path src\cli\output.rs
+// Where the system and its programs write their lines.
+// Everything that prints goes through an `OutputSink`, so output can be captured,
+// redirected to a file or silenced without touching the process' stdout.
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// What a redacted secret is replaced with.
+pub const REDACTED: &str = "********";
+
+/// Secrets shorter than this are only kept out of their own echo. Looked for everywhere,
+/// a short one like `e` or `3` would match ordinary words and colour codes.
+pub const MIN_REDACTED_LEN: usize = 4;
+
+/// A destination for output lines.
+pub trait OutputSink {
+    /// Writes one line, the sink adds the line ending.
+    fn write_line(&mut self, line: &str);
+
+    /// Writes a prompt's label or the answer echoed back, which have to be seen before the answer is typed.
+    /// Most sinks write it like any other line.
+    fn write_prompt(&mut self, line: &str) {
+        self.write_line(line);
+    }
+
+    /// Keeps `secret` out of every line written from now on. Sinks don't need to handle this themselves,
+    /// `shared_output` wraps each one in a sink that does.
+    fn redact(&mut self, _secret: &str) {}
+}
+
+/// An output sink shared between a system and its programs.
+pub type SharedOutput = Arc<Mutex<Box<dyn OutputSink + Send>>>;
+
+/// Shares a sink, replacing any secret read with `input_secret` in what's written to it.
+pub fn shared_output(sink: impl OutputSink + Send + 'static) -> SharedOutput {
+    Arc::new(Mutex::new(Box::new(Redacting { sink, secrets: Vec::new() })))
+}
+
+struct Redacting<S> {
+    sink: S,
+    secrets: Vec<String>,
+}
+
+impl<S> Redacting<S> {
+    fn redacted<'a>(&self, line: &'a str) -> std::borrow::Cow<'a, str> {
+        if !self.secrets.iter().any(|secret| line.contains(secret.as_str())) {
+            return line.into();
+        }
+        // Only the text between escape sequences is looked at, so colour codes are left alone
+        segments(line)
+            .into_iter()
+            .map(|(escape, part)| if escape {
+                part.to_string()
+            } else {
+                self.secrets.iter().fold(part.to_string(), |text, secret| redact_tokens(&text, secret))
+            })
+            .collect::<String>()
+            .into()
+    }
+}
+
+/// Replaces `secret` where it stands as a whole token, not where it's part of a longer word.
+fn redact_tokens(text: &str, secret: &str) -> String {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let mut redacted = String::new();
+    let mut end = 0;
+    for (at, _) in text.match_indices(secret) {
+        let before = text[..at].chars().next_back();
+        let after = text[at + secret.len()..].chars().next();
+        if at < end || before.is_some_and(is_word) || after.is_some_and(is_word) {
+            continue;
+        }
+        redacted.push_str(&text[end..at]);
+        redacted.push_str(REDACTED);
+        end = at + secret.len();
+    }
+    redacted.push_str(&text[end..]);
+    redacted
+}
+
+/// Splits a line into text and ANSI escape sequences, `true` marks an escape sequence.
+fn segments(line: &str) -> Vec<(bool, &str)> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut chars = line.char_indices();
+    while let Some((at, c)) = chars.next() {
+        if c != '\x1b' {
+            continue;
+        }
+        if start < at {
+            parts.push((false, &line[start..at]));
+        }
+        let mut end = at + 1;
+        if let Some((i, next)) = chars.next() {
+            end = i + next.len_utf8();
+            // `ESC [` runs up to a final byte between `@` and `~`, other escapes are one character
+            if next == '[' {
+                for (i, c) in chars.by_ref() {
+                    end = i + c.len_utf8();
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+        }
+        parts.push((true, &line[at..end]));
+        start = end;
+    }
+    if start < line.len() {
+        parts.push((false, &line[start..]));
+    }
+    parts
+}
+
+impl<S: OutputSink> OutputSink for Redacting<S> {
+    fn write_line(&mut self, line: &str) {
+        let line = self.redacted(line);
+        self.sink.write_line(&line);
+    }
+
+    fn write_prompt(&mut self, line: &str) {
+        let line = self.redacted(line);
+        self.sink.write_prompt(&line);
+    }
+
+    fn redact(&mut self, secret: &str) {
+        if secret.chars().count() >= MIN_REDACTED_LEN && !self.secrets.iter().any(|s| s == secret) {
+            self.secrets.push(secret.to_string());
+            // Longest first, so a secret containing another is replaced whole
+            self.secrets.sort_by_key(|s| std::cmp::Reverse(s.len()));
+        }
+        self.sink.redact(secret);
+    }
+}
+
+/// Prints to stdout, the default for every system and program.
+pub struct TerminalOutput;
+
+impl OutputSink for TerminalOutput {
+    fn write_line(&mut self, line: &str) {
+        println!("{}", line);
+    }
+}
+
+/// Keeps every line in memory. Clones share the same buffer,
+/// so keep one around to read what was written after handing the other to a system.
+#[derive(Clone, Default)]
+pub struct BufferOutput {
+    lines: Arc<Mutex<Vec<String>>>,
+}
+
+impl BufferOutput {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        self.lines.lock().unwrap().clone()
+    }
+
+    /// Everything written so far, one line per line.
+    pub fn contents(&self) -> String {
+        self.lines().join("\n")
+    }
+
+    pub fn clear(&self) {
+        self.lines.lock().unwrap().clear();
+    }
+}
+
+impl OutputSink for BufferOutput {
+    fn write_line(&mut self, line: &str) {
+        self.lines.lock().unwrap().push(line.to_string());
+    }
+}
+
+/// Holds a program's lines back while it runs in parallel with others, except for prompts,
+/// which go straight to the system's output so the question is seen before it's answered.
+pub(crate) struct HeldOutput {
+    pub(crate) held: BufferOutput,
+    pub(crate) prompts: SharedOutput,
+}
+
+impl OutputSink for HeldOutput {
+    fn write_line(&mut self, line: &str) {
+        self.held.write_line(line);
+    }
+
+    fn write_prompt(&mut self, line: &str) {
+        self.prompts.lock().unwrap().write_prompt(line);
+    }
+
+    fn redact(&mut self, secret: &str) {
+        self.prompts.lock().unwrap().redact(secret);
+    }
+}
+
+/// Writes every line to a file.
+pub struct FileOutput {
+    file: File,
+}
+
+impl FileOutput {
+    /// Creates the file, truncating it if it already exists.
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self {
+            file: File::create(path)?,
+        })
+    }
+}
+
+impl OutputSink for FileOutput {
+    fn write_line(&mut self, line: &str) {
+        // A failed write has nowhere better to be reported than the output itself.
+        let _ = writeln!(self.file, "{}", line);
+    }
+}
+
+/// Throws everything away.
+pub struct NullOutput;
+
+impl OutputSink for NullOutput {
+    fn write_line(&mut self, _line: &str) {}
+}
@@ -0,0 +1,156 @@
+//--------------------- Picker --------------------------------------------------
+// path src\cli\picker.rs
+// The arrow-key menu. Entries are highlighted with the arrow keys or j/k and run with Enter,
+// `/` starts typing a filter, `?` shows the help, left or h goes back and `:` switches to the typed prompt. The highlighted entry's description is shown below the list.
+// Only used when both stdin and stdout are a terminal, otherwise the numbered menu is shown.
+use console::{Key, Style, Term};
+
+use crate::cli::fuzzy::fuzzy_score;
+
+pub(crate) struct PickerEntry {
+    pub(crate) label: String,
+    pub(crate) description: String,
+}
+
+/// What the picker wants done after a key press.
+#[derive(Debug, PartialEq)]
+pub(crate) enum PickerAction {
+    Continue,
+    /// Run or enter the entry at this index.
+    Pick(usize),
+    Help,
+    /// Go up one level, out of a sub-system.
+    Back,
+    /// Type a line at the normal prompt instead, for meta-commands and arguments.
+    Prompt,
+    Cancel,
+    Quit,
+}
+
+pub(crate) struct Picker {
+    entries: Vec<PickerEntry>,
+    highlighted: usize,
+    filter: Option<String>,
+}
+
+impl Picker {
+    pub(crate) fn new(entries: Vec<PickerEntry>) -> Self {
+        Self { entries, highlighted: 0, filter: None }
+    }
+
+    /// Indices of the entries matching the filter, best match first.
+    pub(crate) fn visible(&self) -> Vec<usize> {
+        let query = match &self.filter {
+            Some(query) if !query.is_empty() => query,
+            _ => return (0..self.entries.len()).collect(),
+        };
+        let mut scored: Vec<(i64, usize)> = self.entries.iter().enumerate()
+            .filter_map(|(i, e)| fuzzy_score(query, &e.label)
+                .max(fuzzy_score(query, &e.description))
+                .map(|score| (score, i)))
+            .collect();
+        scored.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+        scored.into_iter().map(|(_, i)| i).collect()
+    }
+
+    pub(crate) fn highlighted(&self) -> Option<usize> {
+        self.visible().get(self.highlighted).copied()
+    }
+
+    pub(crate) fn handle_key(&mut self, key: Key) -> PickerAction {
+        let count = self.visible().len();
+        let filtering = self.filter.is_some();
+        match key {
+            Key::ArrowUp => self.highlighted = self.highlighted.saturating_sub(1),
+            Key::ArrowDown => self.highlighted = (self.highlighted + 1).min(count.saturating_sub(1)),
+            Key::Char('k') if !filtering => self.highlighted = self.highlighted.saturating_sub(1),
+            Key::Char('j') if !filtering => self.highlighted = (self.highlighted + 1).min(count.saturating_sub(1)),
+            Key::Enter => {
+                return match self.highlighted() {
+                    Some(i) => PickerAction::Pick(i),
+                    None => PickerAction::Continue,
+                };
+            }
+            Key::Char('/') if !filtering => self.filter = Some(String::new()),
+            Key::Char('?') if !filtering => return PickerAction::Help,
+            Key::Char(':') if !filtering => return PickerAction::Prompt,
+            Key::ArrowLeft | Key::Char('h') if !filtering => return PickerAction::Back,
+            Key::Char('q') if !filtering => return PickerAction::Quit,
+            Key::CtrlC => return PickerAction::Quit,
+            Key::Escape if filtering => {
+                self.filter = None;
+                self.highlighted = 0;
+            }
+            Key::Escape => return PickerAction::Cancel,
+            Key::Backspace if filtering => {
+                if let Some(filter) = &mut self.filter {
+                    filter.pop();
+                }
+                self.highlighted = 0;
+            }
+            Key::Char(c) if filtering => {
+                if let Some(filter) = &mut self.filter {
+                    filter.push(c);
+                }
+                self.highlighted = 0;
+            }
+            _ => {}
+        }
+        PickerAction::Continue
+    }
+
+    /// The lines to draw: the entries, a preview of the highlighted one and the key hints.
+    pub(crate) fn render(&self, style: &Style) -> Vec<String> {
+        let visible = self.visible();
+        let mut lines: Vec<String> = visible.iter().enumerate()
+            .map(|(row, i)| {
+                if row == self.highlighted {
+                    format!("{}", style.clone().reverse().apply_to(format!("> {}", self.entries[*i].label)))
+                } else {
+                    format!("  {}", self.entries[*i].label)
+                }
+            })
+            .collect();
+        if visible.is_empty() {
+            lines.push("  No matches".to_string());
+        }
+
+        let preview = self.highlighted().map(|i| self.entries[i].description.as_str()).unwrap_or("");
+        lines.push(format!("{}", Style::new().italic().apply_to(preview)));
+        lines.push(match &self.filter {
+            Some(filter) => format!("/{}  (Esc clears, Enter runs)", filter),
+            None => "↑/↓ or j/k move, Enter runs, ← back, / filters, : prompt, ? help, q quits".to_string(),
+        });
+        lines
+    }
+
+    /// Draws the picker on the terminal and reads keys until something other than moving around happens.
+    pub(crate) fn run(&mut self, term: &Term, style: &Style) -> std::io::Result<PickerAction> {
+        term.hide_cursor()?;
+        let _cursor = CursorGuard(term);
+        let mut drawn = 0;
+        let action = loop {
+            term.clear_last_lines(drawn)?;
+            let lines = self.render(style);
+            for line in &lines {
+                term.write_line(line)?;
+            }
+            drawn = lines.len();
+            match self.handle_key(term.read_key()?) {
+                PickerAction::Continue => continue,
+                action => break action,
+            }
+        };
+        term.clear_last_lines(drawn)?;
+        Ok(action)
+    }
+}
+
+/// Shows the cursor again however the picker ends, errors included.
+struct CursorGuard<'a>(&'a Term);
+
+impl Drop for CursorGuard<'_> {
+    fn drop(&mut self) {
+        let _ = self.0.show_cursor();
+    }
+}
@@ -1,9 +1,43 @@
 // --------------------- Program -------------------------------------------------
 /// Path: src\program_lib.rs
-
 use std::{thread, time};
+use std::time::Instant;
 use console::Style;
 use crate::{TermColor, set_color};
+use crate::cli::args::{Arg, Flag, ParsedArgs};
+use crate::cli::context::Context;
+use crate::cli::input::*;
+use crate::cli::output::*;
+use crate::cli::system::CliError;
+use crate::cli::tags::tag_matches;
+use crate::cli::requirements::Requirement;
+
+/// What a program runs. Implement it on a struct to keep state, like counters or connections, between runs.
+/// Closures taking a `Context` are runnables too.
+/// Runnables are `Send` so programs can be run on worker threads, see `System::run_parallel`.
+pub trait Runnable: Send {
+    fn run(&mut self, ctx: &mut Context) -> Result<(), CliError>;
+
+    /// Called once, before the first run.
+    fn setup(&mut self, _ctx: &mut Context) -> Result<(), CliError> {
+        Ok(())
+    }
+
+    /// Called when the system shuts down, if setup has run: when the user quits the menu or types `quit` at a prompt,
+    /// after `run_args` and from `System::quit`.
+    fn teardown(&mut self, _ctx: &mut Context) -> Result<(), CliError> {
+        Ok(())
+    }
+}
+
+impl<F> Runnable for F
+where
+    F: FnMut(&mut Context) -> Result<(), CliError> + Send,
+{
+    fn run(&mut self, ctx: &mut Context) -> Result<(), CliError> {
+        self(ctx)
+    }
+}
 
 pub trait CommandExecutor {
     fn execute(&self, cmd: &str) -> std::process::ExitStatus;
@@ -31,25 +65,56 @@ impl CommandExecutor for DefaultCommandExecutor {
     }
 }
 
+/// A check run each time it's needed, like whether an environment variable is set.
+pub type Predicate = Box<dyn Fn() -> bool + Send>;
+
 pub struct Program {
     name: String,
-    run_func: Box<dyn Fn()>,
+    runnable: Box<dyn Runnable>,
+    set_up: bool,
     style: Style,
     color: TermColor,
     sleep: u64,
     silent: bool,
     description: String,
+    long_description: String,
+    examples: Vec<String>,
+    aliases: Vec<String>,
     tags: Vec<String>,
+    category: Option<String>,
+    priority: i32,
+    last_run: Option<Instant>,
+    hidden: bool,
+    visible_when: Option<Predicate>,
+    disabled_when: Vec<(Predicate, String)>,
+    requirements: Vec<Requirement>,
+    confirm: Option<String>,
+    args: Vec<Arg>,
+    flags: Vec<Flag>,
+    input: SharedInput,
+    output: SharedOutput,
 }
 
 pub struct ProgramBuilder {
     name: String,
-    run_func: Option<Box<dyn Fn()>>,
+    runnable: Option<Box<dyn Runnable>>,
     color: TermColor,
     sleep: u64,
     silent: bool,
     description: String,
+    long_description: String,
+    examples: Vec<String>,
+    aliases: Vec<String>,
     tags: Vec<String>,
+    category: Option<String>,
+    priority: i32,
+    hidden: bool,
+    visible_when: Option<Predicate>,
+    disabled_when: Vec<(Predicate, String)>,
+    requirements: Vec<Requirement>,
+    confirm: Option<String>,
+    args: Vec<Arg>,
+    flags: Vec<Flag>,
 }
 
 pub struct ShellCommand {
@@ -101,9 +166,10 @@ impl ShellCommand {
                 }
             } else {
                 // Check if command exists in PATH
-                if let Err(_) = std::process::Command::new("which")
+                if std::process::Command::new("which")
                     .arg(&self.base_command)
-                    .output() {
+                    .output()
+                    .is_err() {
                     return Err(ShellCommandError::CommandNotFound(format!("Command {} not found", self.base_command)));
                 }
             }
@@ -112,9 +178,10 @@ impl ShellCommand {
         #[cfg(windows)]
         {
             // On Windows, we'll check if the command exists in PATH
-            if let Err(_) = std::process::Command::new("where")
+            if std::process::Command::new("where")
                 .arg(&self.base_command)
-                .output() {
+                .output()
+                .is_err() {
                 return Err(ShellCommandError::CommandNotFound(format!("Command {} not found", self.base_command)));
             }
         }
@@ -164,43 +231,115 @@ impl Program {
     }
 
     pub fn get_color(&self) -> TermColor {
-        return self.color;
+        self.color
     }
 
     pub fn get_sleep(&self) -> u64 {
-        return self.sleep;
+        self.sleep
     }
 
     pub fn get_silence(&self) -> bool {
-        return self.silent;
+        self.silent
     }
 
     pub fn get_name(&self) -> String {
-        return self.name.clone();
+        self.name.clone()
     }
 
     pub fn get_description(&self) -> String {
-        return self.description.clone();
+        self.description.clone()
+    }
+
+    /// Replaces where this program reads from. Appending it to a system replaces it again with the system's.
+    pub fn set_input_source(&mut self, source: impl InputSource + Send + 'static) {
+        self.input = shared_input(source);
+    }
+
+    /// Replaces where this program writes to. Appending it to a system replaces it again with the system's.
+    pub fn set_output_sink(&mut self, sink: impl OutputSink + Send + 'static) {
+        self.output = shared_output(sink);
+    }
+
+    pub(crate) fn share_input(&mut self, input: SharedInput) {
+        self.input = input;
+    }
+
+    pub(crate) fn share_output(&mut self, output: SharedOutput) {
+        self.output = output;
+    }
+
+    fn write_line(&self, line: impl std::fmt::Display) {
+        self.output.lock().unwrap().write_line(&line.to_string());
     }
 
     pub fn print(&self, message: &str) {
-        println!("{}", self.style.apply_to(format!("{}> {}", self.name, message)));
+        self.write_line(self.style.apply_to(format!("{}> {}", self.name, message)));
         thread::sleep(time::Duration::from_millis(self.sleep));
     }
 
     pub fn err_msg(&self, message: &str) {
-        println!("{}", Style::new().red().apply_to(format!("{} Error> {}", self.name, message)));    
+        self.err(message);
     }
     
-    pub fn run(&self) {
+    pub fn run(&mut self) -> Result<(), CliError> {
+        self.run_with_args(&[])
+    }
+
+    /// Runs the program with a context holding the given arguments, returning what the action returned.
+    /// Programs with declared arguments or flags check them first, see `ParsedArgs::parse`.
+    /// The first run calls the runnable's `setup` beforehand. A disabled program doesn't run
+    /// and returns `CliError::ProgramDisabled`.
+    pub fn run_with_args(&mut self, args: &[String]) -> Result<(), CliError> {
+        if let Some(reason) = self.disabled_reason() {
+            return Err(CliError::ProgramDisabled(format!("{}, {}", self.name, reason)));
+        }
+        let parsed = if self.args.is_empty() && self.flags.is_empty() {
+            ParsedArgs::default()
+        } else {
+            ParsedArgs::parse(&self.args, &self.flags, args)?
+        };
         if !self.silent {
-            println!("{}", self.style.apply_to(format!("{} Running...", self.name)));
+            self.write_line(self.style.apply_to(format!("{} Running...", self.name)));
         }
-        (self.run_func)();
+        self.last_run = Some(Instant::now());
+        if !self.set_up {
+            self.with_context(args, &parsed, |r, ctx| r.setup(ctx))?;
+            self.set_up = true;
+        }
+        self.with_context(args, &parsed, |r, ctx| r.run(ctx))
+    }
+
+    /// Calls the runnable's `teardown` if it has been set up, the next run sets it up again.
+    pub fn teardown(&mut self) -> Result<(), CliError> {
+        if !self.set_up {
+            return Ok(());
+        }
+        self.set_up = false;
+        self.with_context(&[], &ParsedArgs::default(), |r, ctx| r.teardown(ctx))
+    }
+
+    fn with_context<T>(
+        &mut self,
+        args: &[String],
+        parsed: &ParsedArgs,
+        f: impl FnOnce(&mut dyn Runnable, &mut Context) -> T,
+    ) -> T {
+        let mut ctx = Context {
+            name: &self.name,
+            description: &self.description,
+            tags: &self.tags,
+            style: &self.style,
+            sleep: self.sleep,
+            args,
+            parsed,
+            input: &self.input,
+            output: &self.output,
+        };
+        f(self.runnable.as_mut(), &mut ctx)
     }
 
     pub fn err(&self, message: &str) {
-        println!("{}", Style::new().red().apply_to(format!("{} Error> {}", self.name, message)));
+        self.write_line(Style::new().red().apply_to(format!("{} Error> {}", self.name, message)));
     }
 
     pub fn name(&self) -> &str {
@@ -211,16 +350,86 @@ impl Program {
         &self.description
     }
 
+    pub fn long_description(&self) -> &str {
+        &self.long_description
+    }
+
+    pub fn examples(&self) -> &[String] {
+        &self.examples
+    }
+
+    pub fn aliases(&self) -> &[String] {
+        &self.aliases
+    }
+
     pub fn tags(&self) -> &Vec<String> {
         &self.tags
     }
 
+    pub fn category(&self) -> Option<&str> {
+        self.category.as_deref()
+    }
+
+    pub fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    pub fn is_hidden(&self) -> bool {
+        self.hidden
+    }
+
+    /// Whether the program shows up in menus, help and tag selections right now.
+    /// Programs that don't can still be run by their exact name.
+    pub fn is_listed(&self) -> bool {
+        !self.hidden && self.visible_when.as_ref().is_none_or(|visible| visible())
+    }
+
+    /// Why the program can't run right now, the reason of the first `disabled_when` that holds
+    /// or else the first requirement that isn't met.
+    pub fn disabled_reason(&self) -> Option<String> {
+        self.disabled_when.iter()
+            .find(|(disabled, _)| disabled())
+            .map(|(_, reason)| reason.clone())
+            .or_else(|| self.requirements.iter().find(|r| !r.is_met()).map(|r| format!("requires {}", r)))
+    }
+
+    pub fn requirements(&self) -> &[Requirement] {
+        &self.requirements
+    }
+
+    /// The warning shown before the system runs the program, if it asks first.
+    pub fn confirm_message(&self) -> Option<&str> {
+        self.confirm.as_deref()
+    }
+
+    /// When the program was last run, `None` if it hasn't been.
+    pub fn last_run(&self) -> Option<Instant> {
+        self.last_run
+    }
+
     pub fn get_tags(&self) -> Vec<String> {
         self.tags.clone()
     }
 
+    /// Whether the program has the tag or one nested below it, `net` matches `net/dns`.
     pub fn has_tag(&self, tag: &str) -> bool {
-        self.tags.iter().any(|t| t == tag)
+        self.tags.iter().any(|t| tag_matches(t, tag))
+    }
+
+    pub fn args(&self) -> &[Arg] {
+        &self.args
+    }
+
+    pub fn flags(&self) -> &[Flag] {
+        &self.flags
+    }
+
+    /// A usage line built from the declared arguments and flags, e.g. `greet <name> [--loud]`.
+    pub fn usage(&self) -> String {
+        let mut parts = vec![self.name.clone()];
+        parts.extend(self.args.iter().map(Arg::usage));
+        parts.extend(self.flags.iter().map(Flag::usage));
+        parts.join(" ")
     }
     
 }
@@ -230,12 +439,24 @@ impl ProgramBuilder {
     pub fn new(name: impl Into<String>) -> Self {
         Self {
             name: name.into(),
-            run_func: None,
+            runnable: None,
             color: TermColor::Green,
             sleep: 0,
             silent: false,
             description: String::new(),
+            long_description: String::new(),
+            examples: Vec::new(),
+            aliases: Vec::new(),
             tags: Vec::new(),
+            category: None,
+            priority: 0,
+            hidden: false,
+            visible_when: None,
+            disabled_when: Vec::new(),
+            requirements: Vec::new(),
+            confirm: None,
+            args: Vec::new(),
+            flags: Vec::new(),
         }
     }
 
@@ -246,39 +467,36 @@ impl ProgramBuilder {
             .description("")
     }
 
-    pub fn action<F: Fn() + 'static>(mut self, f: F) -> Self {
-        self.run_func = Some(Box::new(f));
+    /// A plain action that ignores its context and can't fail.
+    pub fn action<F: FnMut() + Send + 'static>(self, mut f: F) -> Self {
+        self.action_with_context(move |_: &mut Context| {
+            f();
+            Ok(())
+        })
+    }
+
+    /// An action that gets the context it runs in, its error is returned by `Program::run`.
+    pub fn action_with_context<F>(self, f: F) -> Self
+    where
+        F: FnMut(&mut Context) -> Result<(), CliError> + Send + 'static,
+    {
+        self.runnable(f)
+    }
+
+    /// Runs a `Runnable`, which keeps its state for as long as the program lives.
+    pub fn runnable(mut self, runnable: impl Runnable + 'static) -> Self {
+        self.runnable = Some(Box::new(runnable));
         self
     }
 
-    pub fn shell_command(mut self, cmd: impl Into<String>) -> Self {
+    pub fn shell_command(self, cmd: impl Into<String>) -> Self {
         let command = ShellCommand::new(cmd);
-        self.run_func = Some(Box::new(move || {
-            match command.execute() {
-                Ok(status) => {
-                    if !status.success() {
-                        println!("Command failed with status: {}", status);
-                    }
-                }
-                Err(e) => println!("Command error: {:?}", e),
-            }
-        }));
-        self
+        self.action_with_context(move |_: &mut Context| run_shell_command(&command))
     }
 
-    pub fn dynamic_shell_command(mut self, cmd: impl Into<String>) -> Self {
+    pub fn dynamic_shell_command(self, cmd: impl Into<String>) -> Self {
         let command = ShellCommand::new(cmd);
-        self.run_func = Some(Box::new(move || {
-            match command.execute() {
-                Ok(status) => {
-                    if !status.success() {
-                        println!("Command failed with status: {}", status);
-                    }
-                }
-                Err(e) => println!("Command error: {:?}", e),
-            }
-        }));
-        self
+        self.action_with_context(move |_: &mut Context| run_shell_command(&command))
     }
 
     pub fn color(mut self, color: TermColor) -> Self {
@@ -301,6 +519,24 @@ impl ProgramBuilder {
         self
     }
 
+    /// A longer explanation, shown by `help <program>` under the short description.
+    pub fn long_description(mut self, desc: impl Into<String>) -> Self {
+        self.long_description = desc.into();
+        self
+    }
+
+    /// Another name the program can be picked by, at the menu prompt or on the command line.
+    pub fn alias(mut self, alias: impl Into<String>) -> Self {
+        self.aliases.push(alias.into());
+        self
+    }
+
+    /// An example invocation, e.g. `greet Alice --loud`, shown by `help <program>`.
+    pub fn example(mut self, example: impl Into<String>) -> Self {
+        self.examples.push(example.into());
+        self
+    }
+
     pub fn tags(mut self, tags: Vec<String>) -> Self {
         self.tags = tags;
         self
@@ -311,8 +547,76 @@ impl ProgramBuilder {
         self
     }
 
+    /// The section the program is listed under when the menu is ordered by category.
+    pub fn category(mut self, category: impl Into<String>) -> Self {
+        self.category = Some(category.into());
+        self
+    }
+
+    /// Where the program sorts in the menu, higher comes first. Programs start at 0.
+    pub fn priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Leaves the program out of menus, help and tag selections, it can only be run by its exact name or alias.
+    pub fn hidden(mut self, hidden: bool) -> Self {
+        self.hidden = hidden;
+        self
+    }
+
+    /// Only lists the program while `visible` holds, it's checked every time the menu is drawn.
+    pub fn visible_when<F: Fn() -> bool + Send + 'static>(mut self, visible: F) -> Self {
+        self.visible_when = Some(Box::new(visible));
+        self
+    }
+
+    /// Makes the system ask before running the program, showing the warning, e.g. "This drops the staging DB".
+    /// How it asks is up to the system, see `SystemBuilder::confirm_style`.
+    pub fn confirm(mut self, warning: impl Into<String>) -> Self {
+        self.confirm = Some(warning.into());
+        self
+    }
+
+    /// Needs an executable on the PATH, e.g. `jq`. The program is disabled while it can't be found.
+    pub fn requires_binary(mut self, name: impl Into<String>) -> Self {
+        self.requirements.push(Requirement::Binary(name.into()));
+        self
+    }
+
+    /// Needs an environment variable to be set. The program is disabled while it isn't.
+    pub fn requires_env(mut self, name: impl Into<String>) -> Self {
+        self.requirements.push(Requirement::Env(name.into()));
+        self
+    }
+
+    /// Needs a file or directory to exist. The program is disabled while it doesn't.
+    pub fn requires_file(mut self, path: impl Into<std::path::PathBuf>) -> Self {
+        self.requirements.push(Requirement::File(path.into()));
+        self
+    }
+
+    /// Greys the program out with the reason, e.g. "requires $KUBECONFIG", while `disabled` holds.
+    /// Running it meanwhile is a `CliError::ProgramDisabled`.
+    pub fn disabled_when<F: Fn() -> bool + Send + 'static>(mut self, disabled: F, reason: impl Into<String>) -> Self {
+        self.disabled_when.push((Box::new(disabled), reason.into()));
+        self
+    }
+
+    /// Whether the program has the tag or one nested below it, `net` matches `net/dns`.
     pub fn has_tag(&self, tag: &str) -> bool {
-        self.tags.iter().any(|t| t == tag)
+        self.tags.iter().any(|t| tag_matches(t, tag))
+    }
+
+    /// Declares an argument, positional arguments are filled in the order they're declared.
+    pub fn arg(mut self, arg: Arg) -> Self {
+        self.args.push(arg);
+        self
+    }
+
+    pub fn flag(mut self, flag: Flag) -> Self {
+        self.flags.push(flag);
+        self
     }
 
     pub fn get_tags(&self) -> &Vec<String> {
@@ -322,13 +626,37 @@ impl ProgramBuilder {
     pub fn build(self) -> Program {
         Program {
             name: self.name,
-            run_func: self.run_func.expect("No action set for Program"),
+            runnable: self.runnable.expect("No action set for Program"),
+            set_up: false,
             style: set_color(Style::new(), self.color),
             color: self.color,
             sleep: self.sleep,
             silent: self.silent,
             description: self.description,
+            long_description: self.long_description,
+            examples: self.examples,
+            aliases: self.aliases,
             tags: self.tags,
+            category: self.category,
+            priority: self.priority,
+            last_run: None,
+            hidden: self.hidden,
+            visible_when: self.visible_when,
+            disabled_when: self.disabled_when,
+            requirements: self.requirements,
+            confirm: self.confirm,
+            args: self.args,
+            flags: self.flags,
+            input: shared_input(StdinInput),
+            output: shared_output(TerminalOutput),
         }
     }
 }
+
+fn run_shell_command(command: &ShellCommand) -> Result<(), CliError> {
+    match command.execute() {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(CliError::CommandFailed(format!("Command failed with status: {}", status))),
+        Err(e) => Err(CliError::CommandFailed(format!("Command error: {:?}", e))),
+    }
+}
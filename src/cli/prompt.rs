@@ -0,0 +1,287 @@
+//--------------------- Prompt --------------------------------------------------
+// path src\cli\prompt.rs
+// Typed prompts: the answer is parsed into any `FromStr` type and asked for again, with the reason,
+// until it parses. A prompt can have a default for an empty answer, an allowed range and a retry limit.
+// Also the answer checks behind yes/no questions and picking one or more items from a list,
+// and the `Prompter` that asks all of these for both `System` and `Context`.
+use std::fmt::Display;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+use console::Style;
+
+use crate::cli::input::{Secret, SharedInput};
+use crate::cli::output::{SharedOutput, REDACTED};
+use crate::cli::system::CliError;
+use crate::cli::validate::Validator;
+
+type Check<T> = Box<dyn Fn(&T) -> Result<(), String>>;
+
+/// A question asked through `System::prompt` or `Context::prompt`.
+pub struct Prompt<T> {
+    label: String,
+    default: Option<(T, String)>,
+    checks: Vec<Check<T>>,
+    hint: String,
+    retries: Option<usize>,
+}
+
+impl<T> Prompt<T>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    pub fn new(label: impl Into<String>) -> Self {
+        Self {
+            label: label.into(),
+            default: None,
+            checks: Vec::new(),
+            hint: String::new(),
+            retries: None,
+        }
+    }
+
+    /// The answer when nothing is typed, shown after the label like `[8080]`.
+    pub fn default(mut self, value: T) -> Self
+    where
+        T: Display,
+    {
+        let shown = value.to_string();
+        self.default = Some((value, shown));
+        self
+    }
+
+    /// Only accepts answers from `range`, both ends included, shown after the label like `(1-65535)`.
+    pub fn range(mut self, range: RangeInclusive<T>) -> Self
+    where
+        T: PartialOrd + Display + 'static,
+    {
+        self.hint = format!(" ({}-{})", range.start(), range.end());
+        self.checks.push(Box::new(move |value| {
+            if range.contains(value) {
+                Ok(())
+            } else {
+                Err(format!("{} is not between {} and {}", value, range.start(), range.end()))
+            }
+        }));
+        self
+    }
+
+    /// Gives up with a `CliError::InvalidInput` after this many wrong answers past the first,
+    /// instead of asking until one is right.
+    pub fn retries(mut self, retries: usize) -> Self {
+        self.retries = Some(retries);
+        self
+    }
+
+    /// The label with the range and default added.
+    pub fn full_label(&self) -> String {
+        match &self.default {
+            Some((_, shown)) => format!("{}{} [{}]", self.label, self.hint, shown),
+            None => format!("{}{}", self.label, self.hint),
+        }
+    }
+
+    /// Checks one answer, `Err` holds why it wasn't accepted.
+    fn check(&self, answer: &str) -> Result<Option<T>, String> {
+        let answer = answer.trim();
+        if answer.is_empty() && self.default.is_some() {
+            return Ok(None);
+        }
+        let value: T = answer.parse().map_err(|e| format!("'{}' is not valid, {}", answer, e))?;
+        for check in &self.checks {
+            check(&value)?;
+        }
+        Ok(Some(value))
+    }
+
+    /// Asks until an answer is accepted or the retries run out. `read` prompts with the label and returns the answer,
+    /// `complain` shows why an answer wasn't accepted. A default outside the range is a `CliError::InvalidInput`
+    /// before anything is asked.
+    pub(crate) fn ask(
+        self,
+        mut read: impl FnMut(&str) -> Result<String, CliError>,
+        complain: impl Fn(&str),
+    ) -> Result<T, CliError> {
+        if let Some((value, shown)) = &self.default {
+            for check in &self.checks {
+                check(value).map_err(|reason| CliError::InvalidInput(
+                    format!("The default {} for '{}' is not allowed, {}", shown, self.label, reason)))?;
+            }
+        }
+        let label = self.full_label();
+        let mut attempts = 0;
+        loop {
+            let reason = match self.check(&read(&label)?) {
+                Ok(Some(value)) => return Ok(value),
+                Ok(None) => return Ok(self.default.expect("checked that a default is set").0),
+                Err(reason) => reason,
+            };
+            if self.retries.is_some_and(|retries| attempts >= retries) {
+                return Err(CliError::InvalidInput(reason));
+            }
+            complain(&reason);
+            attempts += 1;
+        }
+    }
+}
+
+/// Reads a yes or no answer, an empty one is `default`.
+pub(crate) fn parse_yes_no(answer: &str, default: bool) -> Result<bool, String> {
+    match answer.trim().to_lowercase().as_str() {
+        "" => Ok(default),
+        "y" | "yes" => Ok(true),
+        "n" | "no" => Ok(false),
+        other => Err(format!("'{}' is not yes or no", other)),
+    }
+}
+
+/// The numbered list shown above a choice prompt, numbered from 1.
+pub(crate) fn choice_lines<T: Display>(items: &[T]) -> Vec<String> {
+    items.iter().enumerate().map(|(i, item)| format!("  {}) {}", i + 1, item)).collect()
+}
+
+/// Picks an item by its number, its name ignoring case, or the start of exactly one name.
+pub(crate) fn parse_choice<T: Display>(items: &[T], answer: &str) -> Result<usize, String> {
+    let answer = answer.trim();
+    if let Ok(number) = answer.parse::<usize>() {
+        return if (1..=items.len()).contains(&number) {
+            Ok(number - 1)
+        } else {
+            Err(format!("{} is not between 1 and {}", number, items.len()))
+        };
+    }
+    let names: Vec<String> = items.iter().map(|item| item.to_string().to_lowercase()).collect();
+    let answer = answer.to_lowercase();
+    if let Some(i) = names.iter().position(|name| *name == answer) {
+        return Ok(i);
+    }
+    let matches: Vec<usize> = (0..names.len()).filter(|i| !answer.is_empty() && names[*i].starts_with(&answer)).collect();
+    match matches.as_slice() {
+        [i] => Ok(*i),
+        [] => Err(format!("'{}' is not one of the options", answer)),
+        _ => Err(format!("'{}' could be any of {}", answer,
+            matches.iter().map(|i| items[*i].to_string()).collect::<Vec<_>>().join(", "))),
+    }
+}
+
+/// Picks any number of items from a comma separated list of numbers, names and ranges like `2-4`.
+/// An empty answer picks nothing. Each item is picked once, in the order given.
+pub(crate) fn parse_choices<T: Display>(items: &[T], answer: &str) -> Result<Vec<usize>, String> {
+    let mut picked: Vec<usize> = Vec::new();
+    for part in answer.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+        let indices = match crate::cli::batch::parse_range(part) {
+            Some((start, end)) if start <= end => (start..=end)
+                .map(|n| parse_choice(items, &n.to_string()))
+                .collect::<Result<Vec<_>, _>>()?,
+            Some(_) => return Err(format!("{} is an empty range", part)),
+            None => vec![parse_choice(items, part)?],
+        };
+        for i in indices {
+            if !picked.contains(&i) {
+                picked.push(i);
+            }
+        }
+    }
+    Ok(picked)
+}
+
+/// The prompts `System` and `Context` both offer, asked through their input and output.
+pub(crate) struct Prompter<'a> {
+    pub(crate) name: &'a str,
+    pub(crate) style: &'a Style,
+    pub(crate) input: &'a SharedInput,
+    pub(crate) output: &'a SharedOutput,
+    /// Shown before the reason an answer wasn't accepted.
+    pub(crate) error_label: String,
+}
+
+impl Prompter<'_> {
+    fn write_prompt(&self, line: impl Display) {
+        self.output.lock().unwrap().write_prompt(&line.to_string());
+    }
+
+    pub(crate) fn complain(&self, reason: &str) {
+        self.write_prompt(Style::new().red().apply_to(format!("{}{}", self.error_label, reason)));
+    }
+
+    /// Prompts and reads a line without treating `quit` as a request to shut down.
+    pub(crate) fn read_line(&self, label: &str) -> Result<String, CliError> {
+        // Held for the whole exchange, so programs running in parallel take turns asking
+        let mut input = self.input.lock().unwrap();
+        self.write_prompt(self.style.apply_to(label));
+        let s = input.read_line()?;
+        self.write_prompt(self.style.apply_to(format!("{}> {}", self.name, s)));
+        Ok(s)
+    }
+
+    pub(crate) fn input(&self, label: &str) -> Result<String, CliError> {
+        let s = self.read_line(label)?;
+        if s.eq("quit") {
+            return Err(CliError::Quit);
+        }
+        Ok(s)
+    }
+
+    pub(crate) fn input_secret(&self, label: &str) -> Result<Secret, CliError> {
+        let mut input = self.input.lock().unwrap();
+        self.write_prompt(self.style.apply_to(label));
+        let secret = input.read_secret()?;
+        self.output.lock().unwrap().redact(&secret);
+        self.write_prompt(self.style.apply_to(format!("{}> {}", self.name, REDACTED)));
+        Ok(Secret::new(secret))
+    }
+
+    pub(crate) fn input_required(&self, label: &str) -> Result<String, CliError> {
+        let input = self.input(label)?;
+        if input.is_empty() {
+            Err(CliError::InvalidInput("Input cannot be empty".to_string()))
+        } else {
+            Ok(input)
+        }
+    }
+
+    pub(crate) fn input_with_validation(&self, label: &str, validator: impl Validator) -> Result<String, CliError> {
+        self.input_until(label, |input| validator.validate(input).map(|_| input.to_string()))
+    }
+
+    /// Prompts until `parse` accepts the answer, showing the reason it gives when it doesn't.
+    fn input_until<T>(&self, label: &str, parse: impl Fn(&str) -> Result<T, String>) -> Result<T, CliError> {
+        loop {
+            match parse(&self.input(label)?) {
+                Ok(value) => return Ok(value),
+                Err(reason) => self.complain(&reason),
+            }
+        }
+    }
+
+    pub(crate) fn confirm(&self, label: &str, default: bool) -> Result<bool, CliError> {
+        let options = if default { "[Y/n]" } else { "[y/N]" };
+        self.input_until(&format!("{} {}", label, options), |answer| parse_yes_no(answer, default))
+    }
+
+    pub(crate) fn select<T: Display>(&self, label: &str, items: &[T]) -> Result<usize, CliError> {
+        if items.is_empty() {
+            return Err(CliError::InvalidInput("Nothing to select from".to_string()));
+        }
+        for line in choice_lines(items) {
+            self.write_prompt(self.style.apply_to(line));
+        }
+        self.input_until(label, |answer| parse_choice(items, answer))
+    }
+
+    pub(crate) fn multi_select<T: Display>(&self, label: &str, items: &[T]) -> Result<Vec<usize>, CliError> {
+        for line in choice_lines(items) {
+            self.write_prompt(self.style.apply_to(line));
+        }
+        self.input_until(&format!("{} (e.g. 1,3 or 2-4)", label), |answer| parse_choices(items, answer))
+    }
+
+    pub(crate) fn prompt<T>(&self, prompt: Prompt<T>) -> Result<T, CliError>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        prompt.ask(|label| self.input(label), |reason| self.complain(reason))
+    }
+}
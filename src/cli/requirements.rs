@@ -0,0 +1,72 @@
+//--------------------- Requirements --------------------------------------------------
+// path src\cli\requirements.rs
+// What a program needs from the machine it runs on: tools on the PATH, environment variables and files.
+// A program with a missing requirement is disabled, and the `doctor` command lists every check.
+use std::env;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Requirement {
+    /// An executable found on the PATH, or at this path if it has a directory in it.
+    Binary(String),
+    /// An environment variable that is set and not empty.
+    Env(String),
+    /// A file or directory that exists.
+    File(PathBuf),
+}
+
+impl Requirement {
+    pub fn is_met(&self) -> bool {
+        match self {
+            Requirement::Binary(name) => find_binary(name).is_some(),
+            Requirement::Env(name) => env::var_os(name).is_some_and(|value| !value.is_empty()),
+            Requirement::File(path) => path.exists(),
+        }
+    }
+}
+
+impl fmt::Display for Requirement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Requirement::Binary(name) => write!(f, "{}", name),
+            Requirement::Env(name) => write!(f, "${}", name),
+            Requirement::File(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+/// Where an executable would be run from, looking through the PATH like a shell does.
+pub fn find_binary(name: &str) -> Option<PathBuf> {
+    let name = Path::new(name);
+    if name.components().count() > 1 {
+        return is_executable(name).then(|| name.to_path_buf());
+    }
+    let extensions: Vec<String> = if cfg!(windows) {
+        env::var("PATHEXT").unwrap_or_else(|_| ".EXE;.BAT;.CMD".to_string())
+            .split(';')
+            .map(str::to_string)
+            .chain(std::iter::once(String::new()))
+            .collect()
+    } else {
+        vec![String::new()]
+    };
+    env::split_paths(&env::var_os("PATH")?)
+        .flat_map(|dir| extensions.iter().map(move |ext| {
+            let mut file = name.as_os_str().to_owned();
+            file.push(ext);
+            dir.join(file)
+        }))
+        .find(|path| is_executable(path))
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata().is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
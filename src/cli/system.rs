@@ -3,15 +3,22 @@
 /// The system is the main print that is intended to be used as the "hub"
 /// The main menu and navigating between the programs. 
 /// All programs are in a list within the system struct.
-
 use std::{thread, time};
 use std::str;
-use console::Style;
-use std::time::SystemTime;
-use gag::Gag;
+use console::{Style, Term};
+use std::time::{Instant, SystemTime};
 
 use crate::*;
 use crate::cli::program::*;
+use crate::cli::input::*;
+use crate::cli::output::*;
+use crate::cli::args::split_args;
+use crate::cli::commands::*;
+use crate::cli::fuzzy::*;
+use crate::cli::batch::*;
+use crate::cli::tags::*;
+use crate::cli::prompt::*;
+use crate::cli::picker::*;
 
 pub struct System {
     name: String,
@@ -19,7 +26,21 @@ pub struct System {
     style: Style,
     sleep: u64,
     programs: Vec<Program>,
+    systems: Vec<System>,
     silent: bool,
+    input: SharedInput,
+    output: SharedOutput,
+    commands: Vec<MetaCommand>,
+    history: Vec<String>,
+    one_based: bool,
+    filter: Option<String>,
+    menu_mode: MenuMode,
+    stop_on_failure: bool,
+    workers: usize,
+    grouping: MenuGrouping,
+    order: MenuOrder,
+    confirm_tags: Vec<String>,
+    confirm_style: ConfirmStyle,
 }
 
 pub struct SystemBuilder {
@@ -28,6 +49,18 @@ pub struct SystemBuilder {
     sleep: u64,
     silent: bool,
     programs: Vec<Program>,
+    systems: Vec<System>,
+    input: SharedInput,
+    output: SharedOutput,
+    commands: Vec<MetaCommand>,
+    one_based: bool,
+    menu_mode: MenuMode,
+    stop_on_failure: bool,
+    workers: usize,
+    grouping: MenuGrouping,
+    order: MenuOrder,
+    confirm_tags: Vec<String>,
+    confirm_style: ConfirmStyle,
 }
 
 // First, let's define our error types
@@ -36,6 +69,15 @@ pub enum CliError {
     InvalidInput(String),
     CommandFailed(String),
     ProgramNotFound(String),
+    /// The program can't run right now, holding its name and why.
+    ProgramDisabled(String),
+    /// The user didn't confirm running a program that asks first, holding its name.
+    NotConfirmed(String),
+    /// The input source ran out of lines, e.g. stdin was closed.
+    EndOfInput,
+    /// `quit` was typed at a prompt. `run` tears everything down and leaves when a program returns it.
+    Quit,
+    Io(String),
     // Add more as needed
 }
 
@@ -45,12 +87,124 @@ impl std::fmt::Display for CliError {
             CliError::InvalidInput(msg) => write!(f, "Invalid input: {}", msg),
             CliError::CommandFailed(msg) => write!(f, "Command failed: {}", msg),
             CliError::ProgramNotFound(msg) => write!(f, "Program not found: {}", msg),
+            CliError::ProgramDisabled(msg) => write!(f, "Program disabled: {}", msg),
+            CliError::NotConfirmed(msg) => write!(f, "Not confirmed: {}", msg),
+            CliError::EndOfInput => write!(f, "End of input"),
+            CliError::Quit => write!(f, "Quit"),
+            CliError::Io(msg) => write!(f, "IO error: {}", msg),
         }
     }
 }
 
 impl std::error::Error for CliError {}
 
+impl CliError {
+    /// The process exit status for this error, 2 for usage errors and 1 for everything else.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CliError::InvalidInput(_) | CliError::ProgramNotFound(_) => 2,
+            _ => 1,
+        }
+    }
+}
+
+/// The result of a single pass through the menu.
+#[derive(Debug)]
+pub enum MenuOutcome {
+    /// A program was run, holding its index and the result of the run.
+    Ran(usize, Result<(), CliError>),
+    /// The user asked to leave the menu.
+    Quit,
+    /// The benchmark was run instead of a program.
+    Bench,
+    /// The input could not be turned into a choice, holding what was typed.
+    InvalidInput(String),
+    /// Nothing was typed, so nothing was run.
+    Cancelled,
+    /// A sub-system was picked, holding its index in `systems()`.
+    Entered(usize),
+    /// The user asked to go up one level.
+    Back,
+    /// The user asked to go back to the top level.
+    Home,
+    /// Help was shown instead of running a program.
+    Help,
+    /// Any other meta-command ran, holding its name and result.
+    Command(String, Result<(), CliError>),
+    /// What was typed is the start of more than one name, holding the candidates.
+    Ambiguous(Vec<String>),
+    /// A `/` search narrowed the menu, holding the query, empty when the search was cleared.
+    Filtered(String),
+    /// Several programs were picked with a list, range or tag and run in order, holding how each went.
+    Batch(Vec<BatchResult>),
+}
+
+impl MenuOutcome {
+    /// Whether the user asked to leave, at the menu or by typing `quit` at a prompt while a program ran.
+    pub fn is_quit(&self) -> bool {
+        match self {
+            MenuOutcome::Quit => true,
+            MenuOutcome::Ran(_, Err(CliError::Quit)) | MenuOutcome::Command(_, Err(CliError::Quit)) => true,
+            MenuOutcome::Batch(results) => results.iter().any(|r| matches!(r.status, BatchStatus::Failed(CliError::Quit))),
+            _ => false,
+        }
+    }
+}
+
+/// How the menu is drawn and picked from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MenuMode {
+    /// A numbered list and a prompt to type at.
+    Numbered,
+    /// A list moved through with the arrow keys. Falls back to `Numbered` when stdin or stdout isn't a terminal.
+    Arrows,
+}
+
+/// How the numbered menu lays out its programs.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MenuGrouping {
+    /// One list in the order the programs were added.
+    Flat,
+    /// Under a heading for each top-level tag, `net` for `net/dns`. Programs with several are listed under each.
+    Tag,
+    /// Under a heading for each category, uncategorised programs last.
+    Category,
+}
+
+/// The order programs are listed in. The number beside a program doesn't change with the order.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MenuOrder {
+    /// The order they were added in.
+    Added,
+    /// By category, under a heading for each, highest priority first within one. Programs without one come last.
+    Category,
+    /// Highest priority first.
+    Priority,
+    Name,
+    /// The most recently run first, then the ones that haven't been run.
+    RecentlyUsed,
+}
+
+/// How the system asks before running a program that needs confirming.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ConfirmStyle {
+    /// A `[y/N]` question, anything but y or yes declines.
+    YesNo,
+    /// The program's name has to be typed out.
+    TypeName,
+}
+
+/// What a word typed at the menu prompt refers to.
+#[derive(Debug, PartialEq)]
+pub enum Selection {
+    Program(usize),
+    System(usize),
+    Ambiguous(Vec<String>),
+    NotFound,
+}
+
+type ProgramFilter = Box<dyn Fn(&Program) -> bool>;
+
 impl System {
     pub fn builder(name: impl Into<String>) -> SystemBuilder {
         SystemBuilder::new(name)
@@ -69,72 +223,889 @@ impl System {
         self.silent = silent;
     }
 
+    /// Numbers the menu from 1 instead of 0.
+    pub fn set_one_based(&mut self, one_based: bool) {
+        self.one_based = one_based;
+    }
+
+    pub fn set_menu_mode(&mut self, mode: MenuMode) {
+        self.menu_mode = mode;
+    }
+
+    /// Whether a batch stops at the first failing program, the rest are then reported as skipped.
+    pub fn set_stop_on_failure(&mut self, stop: bool) {
+        self.stop_on_failure = stop;
+    }
+
+    /// How many programs a `&` batch at the menu prompt runs at once.
+    pub fn set_workers(&mut self, workers: usize) {
+        self.workers = workers;
+    }
+
+    pub fn set_grouping(&mut self, grouping: MenuGrouping) {
+        self.grouping = grouping;
+    }
+
+    pub fn set_menu_order(&mut self, order: MenuOrder) {
+        self.order = order;
+    }
+
     pub fn get_sleep(&self) -> u64 {
         self.sleep
     }
 
+    /// Replaces where prompts read from, e.g. a `ScriptedInput` in tests.
+    pub fn set_input_source(&mut self, source: impl InputSource + Send + 'static) {
+        self.input = shared_input(source);
+        self.share_io();
+    }
+
+    /// Replaces where the system, its programs and its sub-systems write to.
+    pub fn set_output_sink(&mut self, sink: impl OutputSink + Send + 'static) {
+        self.output = shared_output(sink);
+        self.share_io();
+    }
+
+    /// Hands this system's input and output to its programs and sub-systems.
+    fn share_io(&mut self) {
+        for p in &mut self.programs {
+            p.share_input(self.input.clone());
+            p.share_output(self.output.clone());
+        }
+        for sys in &mut self.systems {
+            sys.input = self.input.clone();
+            sys.output = self.output.clone();
+            sys.share_io();
+        }
+    }
+
+    fn write_line(&self, line: impl std::fmt::Display) {
+        self.output.lock().unwrap().write_line(&line.to_string());
+    }
+
     pub fn get_silence(&self) -> bool {
         self.silent
     }
     
+    /// Shows every program, sub-system and built-in command, generated from what the builders were given.
     pub fn show_help(&self) {
-        println!("{}", self.style.apply_to(format!("=== {} Help ===", self.name)));
-        for program in &self.programs {
-            println!("{}", self.style.apply_to(format!("\n{}", program.name())));
-            println!("  Description: {}", program.description());
-            if !program.tags().is_empty() {
-                println!("  Tags: {}", program.tags().join(", "));
-            }
+        self.write_line(self.style.apply_to(format!("=== {} Help ===", self.name)));
+        for program in self.programs.iter().filter(|p| p.is_listed()) {
+            self.write_program_help(program, false);
         }
+        for sys in &self.systems {
+            self.write_line(self.style.apply_to(format!("\n{} >", sys.name())));
+            self.write_line(format!("  Sub-system with {} programs", sys.programs().len()));
+        }
+        self.write_line(self.style.apply_to("\nCommands"));
+        for command in &self.commands {
+            self.write_line(format!("  {0: <20} {1}", command.get_usage(), command.get_help()));
+        }
+        self.write_line(format!("  {0: <20} {1}", "/[query]", "Narrow the menu to matching programs, / alone clears it"));
+        self.write_line(format!("  {0: <20} {1}", "1,3,5-7 | #tag", "Run several programs in order and summarise how they went"));
+        self.write_line(format!("  {0: <20} {1}", "&1,3,5-7 | &#tag", "Run several programs in parallel"));
+    }
+
+    /// Shows everything about one program, picked by name or number,
+    /// including its long description and examples.
+    pub fn show_program_help(&self, name: &str) -> Result<(), CliError> {
+        let index = match self.resolve(name) {
+            Selection::Program(i) => i,
+            Selection::Ambiguous(names) => {
+                return Err(CliError::InvalidInput(format!("{} could be any of {}", name, names.join(", "))));
+            }
+            _ => return Err(CliError::ProgramNotFound(name.to_string())),
+        };
+        let program = &self.programs[index];
+        self.write_line(self.style.apply_to(format!("=== {} Help ===", program.name())));
+        self.write_program_help(program, true);
+        Ok(())
     }
 
+    fn write_program_help(&self, program: &Program, detailed: bool) {
+        self.write_line(self.style.apply_to(format!("\n{}", program.name())));
+        self.write_line(format!("  Description: {}", program.description()));
+        if detailed && !program.long_description().is_empty() {
+            for line in program.long_description().lines() {
+                self.write_line(format!("  {}", line));
+            }
+        }
+        if !program.aliases().is_empty() {
+            self.write_line(format!("  Aliases: {}", program.aliases().join(", ")));
+        }
+        if !program.tags().is_empty() {
+            self.write_line(format!("  Tags: {}", program.tags().join(", ")));
+        }
+        if let Some(category) = program.category() {
+            self.write_line(format!("  Category: {}", category));
+        }
+        if let Some(reason) = program.disabled_reason() {
+            self.write_line(format!("  Disabled: {}", reason));
+        }
+        if detailed || !program.args().is_empty() || !program.flags().is_empty() {
+            self.write_line(format!("  Usage: {}", program.usage()));
+        }
+        for arg in program.args() {
+            let mut details = vec![arg.get_kind().to_string()];
+            if let Some(default) = arg.get_default() {
+                details.push(format!("default {}", default));
+            }
+            if arg.is_required() {
+                details.push("required".to_string());
+            }
+            self.write_line(format!("    {0: <20} {1} ({2})", arg.usage(), arg.get_help(), details.join(", ")));
+        }
+        for flag in program.flags() {
+            self.write_line(format!("    {0: <20} {1}", flag.usage(), flag.get_help()));
+        }
+        if detailed && !program.examples().is_empty() {
+            self.write_line("  Examples:");
+            for example in program.examples() {
+                self.write_line(format!("    {}", example));
+            }
+        }
+    }
 
-    pub fn menu_complex_filter(&mut self, filter: Option<Box<dyn Fn(&Program) -> bool>>) -> Option<usize>{
+    pub fn menu_complex_filter(&mut self, filter: Option<ProgramFilter>) -> MenuOutcome {
         let filtered_indices: Vec<usize> = match filter {
-            Some(f) => self.programs.iter().enumerate().filter(|(_, p)| f(p)).map(|(i, _)| i).collect(),
-            None => self.programs.iter().enumerate().map(|(i, _)| i).collect(),
+            Some(f) => self.listed().filter(|i| f(&self.programs[*i])).collect(),
+            None => self.listed().collect(),
+        };
+
+        let label = format!("{} > Pick program to launch:", self.name);
+        print_menu(self, filtered_indices, &label)
+    }
+
+    /// Shows the menu with only the programs matching a tag query, like `db & !slow`, see `TagExpr`.
+    pub fn menu_with_tags_filter(&mut self, query: &str) -> Result<MenuOutcome, CliError> {
+        let filtered_indices = self.tagged_indices(query)?;
+        let label = format!("{} > Pick program to launch:", self.name);
+        Ok(print_menu(self, filtered_indices, &label))
+    }
+
+    /// Shows the menu once and runs at most one program.
+    /// Sub-systems are listed after the programs, picking one returns `MenuOutcome::Entered`.
+    pub fn menu(&mut self) -> MenuOutcome {
+        let crumbs = format!("{} > ", self.name);
+        self.menu_with_breadcrumb(&crumbs)
+    }
+
+    fn menu_with_breadcrumb(&mut self, crumbs: &str) -> MenuOutcome {
+        if self.menu_mode == MenuMode::Arrows && self.input.lock().unwrap().is_terminal() && Term::stdout().is_term() {
+            return self.arrow_menu(crumbs);
+        }
+        let (indices, label) = match &self.filter {
+            Some(query) => {
+                let indices = self.search_entries(query);
+                if indices.is_empty() {
+                    self.write_line(format!("No matches for {}, type / to clear the search", query));
+                }
+                (indices, format!("{}/{} > Pick program to launch:", crumbs, query))
+            }
+            None => (self.listed().chain(self.programs.len()..self.programs.len() + self.systems.len()).collect(),
+                format!("{}Pick program to launch:", crumbs)),
+        };
+        print_menu(self, indices, &label)
+    }
+
+    fn arrow_menu(&mut self, crumbs: &str) -> MenuOutcome {
+        // Picker entries are in menu order, `order` maps them back to entry indices
+        let mut order: Vec<usize> = self.listed().collect();
+        sort_programs(self, &mut order);
+        order.extend(self.programs.len()..self.programs.len() + self.systems.len());
+        let entries: Vec<PickerEntry> = order.iter()
+            .map(|&i| match self.programs.get(i) {
+                Some(p) => match p.disabled_reason() {
+                    Some(reason) => PickerEntry { label: format!("{} (disabled)", p.name()), description: reason },
+                    None => PickerEntry { label: p.get_name(), description: p.get_description() },
+                },
+                None => PickerEntry {
+                    label: format!("{} >", self.systems[i - self.programs.len()].name()),
+                    description: "sub-system".to_string(),
+                },
+            })
+            .collect();
+
+        self.write_line(self.style.apply_to(crumbs.trim_end()));
+        let action = Picker::new(entries).run(&Term::stdout(), &self.style)
+            .map(|action| match action {
+                PickerAction::Pick(row) => PickerAction::Pick(order[row]),
+                action => action,
+            });
+        match action {
+            Ok(PickerAction::Pick(i)) if i < self.programs.len() => {
+                let result = self.run_program(i);
+                self.report(&result);
+                MenuOutcome::Ran(i, result)
+            }
+            Ok(PickerAction::Pick(i)) => MenuOutcome::Entered(i - self.programs.len()),
+            Ok(PickerAction::Help) => {
+                self.show_help();
+                MenuOutcome::Help
+            }
+            Ok(PickerAction::Back) => MenuOutcome::Back,
+            Ok(PickerAction::Prompt) => match self.read_input(&format!("{}Type a command:", crumbs)) {
+                Ok(input) => self.handle_menu_input(&input),
+                Err(_) => MenuOutcome::Cancelled,
+            },
+            Ok(PickerAction::Quit) => MenuOutcome::Quit,
+            Ok(_) => MenuOutcome::Cancelled,
+            Err(e) => {
+                self.err(Some(&e.to_string()));
+                MenuOutcome::Cancelled
+            }
+        }
+    }
+
+    /// Programs matching the query by name, alias, description or tag, best match first.
+    pub fn search(&self, query: &str) -> Vec<usize> {
+        let mut scored: Vec<(i64, usize)> = self.programs.iter().enumerate()
+            .filter(|(_, p)| p.is_listed())
+            .filter_map(|(i, p)| {
+                let names = std::iter::once(p.name()).chain(p.aliases().iter().map(String::as_str));
+                // Matches on a name count double, so they rank above matches in descriptions and tags
+                let by_name = names.filter_map(|n| fuzzy_score(query, n)).map(|s| s * 2).max();
+                let by_other = std::iter::once(p.description()).chain(p.tags().iter().map(String::as_str))
+                    .filter_map(|t| fuzzy_score(query, t))
+                    .max();
+                by_name.max(by_other).map(|score| (score, i))
+            })
+            .collect();
+        scored.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+        scored.into_iter().map(|(_, i)| i).collect()
+    }
+
+    /// Menu entries matching the query, the programs from `search` followed by sub-systems matching by name.
+    /// A query starting with `#` is a tag query instead and only lists programs.
+    fn search_entries(&self, query: &str) -> Vec<usize> {
+        if let Some(tags) = query.strip_prefix('#') {
+            return self.tagged_indices(tags).unwrap_or_default();
+        }
+        let mut entries = self.search(query);
+        entries.extend(self.systems.iter().enumerate()
+            .filter(|(_, s)| fuzzy_score(query, s.name()).is_some())
+            .map(|(i, _)| i + self.programs.len()));
+        entries
+    }
+
+    /// Names and aliases closest to a mistyped word, closest first.
+    pub fn suggest(&self, word: &str) -> Vec<String> {
+        let names = self.programs.iter()
+            .filter(|p| p.is_listed())
+            .flat_map(|p| std::iter::once(p.name()).chain(p.aliases().iter().map(String::as_str)))
+            .chain(self.systems.iter().map(|s| s.name()));
+        suggestions(word, names, 3)
+    }
+
+    /// The main loop, re-displays the menu after each program finishes until the user quits.
+    /// Handles moving in and out of sub-systems, `back` goes up one level and `home` to the top.
+    pub fn run(&mut self) -> MenuOutcome {
+        let mut path: Vec<usize> = Vec::new();
+        loop {
+            let (current, crumbs) = self.descend(&path);
+            match current.menu_with_breadcrumb(&crumbs) {
+                MenuOutcome::Entered(i) => path.push(i),
+                MenuOutcome::Back => {
+                    path.pop();
+                }
+                MenuOutcome::Home => path.clear(),
+                outcome if outcome.is_quit() => {
+                    self.teardown();
+                    self.write_line("\n\n\t Shutting Down\n\n");
+                    return MenuOutcome::Quit;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Follows a path of sub-system indices, returning the system at the end and its breadcrumb.
+    fn descend(&mut self, path: &[usize]) -> (&mut System, String) {
+        let mut crumbs = format!("{} > ", self.name);
+        let mut current = self;
+        for &i in path {
+            current = &mut current.systems[i];
+            crumbs += &format!("{} > ", current.name);
+        }
+        (current, crumbs)
+    }
+
+    /// Runs the program named by the process' arguments and exits with its status,
+    /// or shows the interactive menu when there are none. See `run_args`.
+    pub fn run_from_env(&mut self) -> ! {
+        let code = self.run_args(std::env::args().skip(1));
+        std::process::exit(code);
+    }
+
+    /// Runs straight from command line arguments, not including the binary name, and returns the exit status.
+    /// `greet` runs the program named greet, `db migrate --dry-run` runs migrate in the db sub-system with `--dry-run`
+    /// as its argument and `--help` lists what can be run. Without arguments it falls back to `run`, while a sub-system's
+    /// name with nothing after it shows that sub-system's help and returns 2.
+    /// `--yes` before the program's name or straight after it answers yes to the confirmation of a program that asks first,
+    /// unless the program declares a `yes` flag of its own. Anywhere else it's left for the program.
+    pub fn run_args<I, S>(&mut self, args: I) -> i32
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let args: Vec<String> = args.into_iter().map(Into::into).collect();
+        if args.is_empty() {
+            self.run();
+            return 0;
+        }
+        let result = self.dispatch(&args, false);
+        self.teardown();
+        match result {
+            Ok(()) | Err(CliError::Quit) => 0,
+            Err(e) => {
+                self.err(Some(&e.to_string()));
+                e.exit_code()
+            }
+        }
+    }
+
+    fn dispatch(&mut self, args: &[String], yes: bool) -> Result<(), CliError> {
+        let (yes, args) = match args.split_first() {
+            Some((first, rest)) if first == "--yes" => (true, rest),
+            _ => (yes, args),
+        };
+        // Only a bare `mytool` opens the menu, see `run_args`. A sub-system's name on its own or a lone `--yes`
+        // is a usage error in a script rather than a menu waiting for input.
+        let (name, rest) = match args.split_first() {
+            Some(split) => split,
+            None => {
+                self.show_help();
+                return Err(CliError::InvalidInput(format!("{} needs a program to run", self.name)));
+            }
+        };
+        if name == "--help" || name == "-h" {
+            self.show_help();
+            return Ok(());
+        }
+        if name == "help" {
+            return match rest.first() {
+                Some(sub) => match (self.find_program(sub), self.find_system(sub)) {
+                    (None, Some(i)) => {
+                        self.systems[i].show_help();
+                        Ok(())
+                    }
+                    _ => self.show_program_help(sub),
+                },
+                None => {
+                    self.show_help();
+                    Ok(())
+                }
+            };
+        }
+        if let Some(i) = self.find_program(name) {
+            if rest.iter().any(|a| a == "--help" || a == "-h") {
+                return self.show_program_help(name);
+            }
+            let own_flag = self.programs[i].flags().iter().any(|f| f.name() == "yes");
+            let (yes, rest) = match rest.split_first() {
+                Some((first, after)) if first == "--yes" && !own_flag => (true, after),
+                _ => (yes, rest),
+            };
+            if !yes {
+                self.confirm_run(i)?;
+            }
+            return self.programs[i].run_with_args(rest);
+        }
+        if let Some(i) = self.find_system(name) {
+            return self.systems[i].dispatch(rest, yes);
+        }
+        Err(CliError::ProgramNotFound(name.to_string()))
+    }
+
+    /// Turns a line typed at the menu prompt into an outcome, running the chosen program if there is one.
+    /// Meta-commands are checked first. Otherwise the first word picks a program by number or name,
+    /// anything after it is passed to the program as arguments.
+    pub(crate) fn handle_menu_input(&mut self, input: &str) -> MenuOutcome {
+        let tokens = split_args(input);
+        let (first, rest) = match tokens.split_first() {
+            Some(split) => split,
+            None => return MenuOutcome::Cancelled,
         };
+        self.history.push(input.to_string());
+        if let Some(query) = input.trim().strip_prefix('/') {
+            let query = query.trim().to_string();
+            if let Some(Err(e)) = query.strip_prefix('#').map(TagExpr::parse) {
+                self.err(Some(&e.to_string()));
+                return MenuOutcome::InvalidInput(input.to_string());
+            }
+            self.filter = if query.is_empty() { None } else { Some(query.clone()) };
+            return MenuOutcome::Filtered(query);
+        }
+        if let Some(i) = self.commands.iter().position(|c| c.name() == first) {
+            return self.run_command(i, rest);
+        }
+        if let Some(list) = input.trim().strip_prefix('&') {
+            return match self.select_many(list) {
+                Ok(indices) => {
+                    self.filter = None;
+                    MenuOutcome::Batch(self.run_parallel(&indices, self.workers))
+                }
+                Err(e) => {
+                    self.err(Some(&e.to_string()));
+                    MenuOutcome::InvalidInput(input.to_string())
+                }
+            };
+        }
+        if is_batch(input) {
+            return match self.select_many(input) {
+                Ok(indices) => {
+                    self.filter = None;
+                    MenuOutcome::Batch(self.run_batch(&indices))
+                }
+                Err(e) => {
+                    self.err(Some(&e.to_string()));
+                    MenuOutcome::InvalidInput(input.to_string())
+                }
+            };
+        }
 
-        return print_menu(self, filtered_indices)
+        match self.resolve(first) {
+            Selection::Program(prog) => {
+                self.filter = None;
+                let result = self.run_confirmed(prog, rest);
+                self.report(&result);
+                MenuOutcome::Ran(prog, result)
+            }
+            Selection::System(i) => MenuOutcome::Entered(i),
+            Selection::Ambiguous(names) => {
+                self.write_line(format!("{} could be any of: {}", first, names.join(", ")));
+                MenuOutcome::Ambiguous(names)
+            }
+            Selection::NotFound => {
+                let suggestions = self.suggest(first);
+                if suggestions.is_empty() {
+                    self.write_line("invalid input");
+                } else {
+                    self.write_line(format!("{} not found, did you mean: {}?", first, suggestions.join(", ")));
+                }
+                MenuOutcome::InvalidInput(input.to_string())
+            }
+        }
     }
 
-    pub fn menu_with_tags_filter(&mut self, tags: Vec<String>) -> Option<usize> {
-        let filtered_indices: Vec<usize> = self.programs.iter().enumerate()
-            .filter(|(_, p)| tags.iter().all(|tag| p.tags().contains(tag)))
+    /// Works out what a word refers to: the number shown beside an entry, an exact name or alias,
+    /// or the start of exactly one name or alias.
+    pub fn resolve(&self, word: &str) -> Selection {
+        if let Ok(number) = word.parse::<usize>() {
+            let entry = if self.one_based { number.checked_sub(1) } else { Some(number) };
+            return match entry {
+                Some(i) if i < self.programs.len() && self.programs[i].is_listed() => Selection::Program(i),
+                Some(i) if i < self.programs.len() => Selection::NotFound,
+                Some(i) if i < self.programs.len() + self.systems.len() => Selection::System(i - self.programs.len()),
+                _ => Selection::NotFound,
+            };
+        }
+        if let Some(i) = self.find_program(word) {
+            return Selection::Program(i);
+        }
+        if let Some(i) = self.find_system(word) {
+            return Selection::System(i);
+        }
+
+        let programs: Vec<usize> = self.programs.iter().enumerate()
+            .filter(|(_, p)| p.is_listed())
+            .filter(|(_, p)| p.name().starts_with(word) || p.aliases().iter().any(|a| a.starts_with(word)))
             .map(|(i, _)| i)
             .collect();
-        print_menu(self, filtered_indices);
-        None
+        let systems: Vec<usize> = self.systems.iter().enumerate()
+            .filter(|(_, s)| s.name().starts_with(word))
+            .map(|(i, _)| i)
+            .collect();
+        match (programs.as_slice(), systems.as_slice()) {
+            ([i], []) => Selection::Program(*i),
+            ([], [i]) => Selection::System(*i),
+            ([], []) => Selection::NotFound,
+            _ => Selection::Ambiguous(programs.iter().map(|i| self.programs[*i].get_name())
+                .chain(systems.iter().map(|i| self.systems[*i].name().to_string()))
+                .collect()),
+        }
     }
-    
 
-    pub fn menu(&mut self) -> Option<usize> {
-        let indices = self.programs.iter().enumerate().map(|(i, _)| i).collect();
-        return print_menu(self, indices);
+    /// Works out which programs a list like `1,3,5-7`, `deploy,test` or `#nightly & !slow` refers to, in the order given.
+    /// Numbers follow the menu's numbering, programs picked more than once only run once.
+    pub fn select_many(&self, input: &str) -> Result<Vec<usize>, CliError> {
+        let mut indices: Vec<usize> = Vec::new();
+        for item in input.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+            let picked: Vec<usize> = if let Some(query) = item.strip_prefix('#') {
+                let tagged = self.tagged_indices(query)?;
+                if tagged.is_empty() {
+                    return Err(CliError::ProgramNotFound(format!("No programs match {}", query)));
+                }
+                tagged
+            } else if let Some((start, end)) = parse_range(item) {
+                if start > end {
+                    return Err(CliError::InvalidInput(format!("{} is an empty range", item)));
+                }
+                let mut picked = Vec::new();
+                for n in start..=end {
+                    match self.resolve(&n.to_string()) {
+                        Selection::Program(i) => picked.push(i),
+                        // Sub-systems and hidden programs inside a range are passed over
+                        Selection::System(_) => {}
+                        Selection::NotFound if self.program_at(n).is_some() => {}
+                        _ => return Err(CliError::ProgramNotFound(n.to_string())),
+                    }
+                }
+                picked
+            } else {
+                vec![self.select_one(item)?]
+            };
+            for i in picked {
+                if !indices.contains(&i) {
+                    indices.push(i);
+                }
+            }
+        }
+        Ok(indices)
+    }
+
+    /// The program shown with this number in the menu, if it's a program.
+    fn program_at(&self, number: usize) -> Option<&Program> {
+        let entry = if self.one_based { number.checked_sub(1)? } else { number };
+        self.programs.get(entry)
+    }
+
+    fn select_one(&self, word: &str) -> Result<usize, CliError> {
+        match self.resolve(word) {
+            Selection::Program(i) => Ok(i),
+            Selection::System(i) => Err(CliError::InvalidInput(format!("{} is a sub-system, not a program", self.systems[i].name()))),
+            Selection::Ambiguous(names) => Err(CliError::InvalidInput(format!("{} could be any of {}", word, names.join(", ")))),
+            Selection::NotFound => Err(CliError::ProgramNotFound(word.to_string())),
+        }
+    }
+
+    /// Runs the programs in order and prints a summary table of how each went and how long it took.
+    /// Disabled and declined programs are skipped without stopping the batch.
+    pub fn run_batch(&mut self, indices: &[usize]) -> Vec<BatchResult> {
+        let mut results: Vec<BatchResult> = Vec::new();
+        let mut failed = false;
+        let mut quit = false;
+        for &i in indices {
+            if i >= self.programs.len() {
+                results.push(self.missing_program(i));
+                failed = true;
+                continue;
+            }
+            let name = self.programs[i].get_name();
+            if quit || (failed && self.stop_on_failure) {
+                results.push(BatchResult { index: i, name, status: BatchStatus::Skipped, duration: Default::default() });
+                continue;
+            }
+            if !self.is_runnable(i) {
+                results.push(BatchResult { index: i, name, status: BatchStatus::Skipped, duration: Default::default() });
+                continue;
+            }
+            let start = Instant::now();
+            let result = self.programs[i].run_with_args(&[]);
+            let duration = start.elapsed();
+            let status = match result {
+                Ok(()) => BatchStatus::Passed,
+                Err(CliError::Quit) => {
+                    quit = true;
+                    BatchStatus::Failed(CliError::Quit)
+                }
+                Err(e) => {
+                    self.err(Some(&e.to_string()));
+                    failed = true;
+                    BatchStatus::Failed(e)
+                }
+            };
+            results.push(BatchResult { index: i, name, status, duration });
+        }
+
+        self.write_line(self.style.apply_to("\nSummary"));
+        for line in summary_lines(&results) {
+            self.write_line(line);
+        }
+        results
+    }
+
+    /// Runs the programs on up to `max_workers` threads at once. Each program's output is held back and
+    /// printed as one labelled block when it finishes, so programs running side by side don't interleave.
+    /// Prompts still read the system's input, one program at a time. Everything a prompt writes, the label, the options,
+    /// why an answer was turned down and the echoed answer, is written straight away instead of being held back.
+    /// Programs that need confirming are asked about before any start, the declined ones are skipped like disabled ones.
+    pub fn run_parallel(&mut self, indices: &[usize], max_workers: usize) -> Vec<BatchResult> {
+        let mut not_run: Vec<BatchResult> = Vec::new();
+        for &i in indices {
+            if i >= self.programs.len() {
+                not_run.push(self.missing_program(i));
+            } else if !self.is_runnable(i) {
+                not_run.push(BatchResult { index: i, name: self.programs[i].get_name(), status: BatchStatus::Skipped, duration: Default::default() });
+            }
+        }
+
+        let output = self.output.clone();
+        let mut queue: Vec<(usize, &mut Program)> = self.programs.iter_mut().enumerate()
+            .filter(|(i, _)| indices.contains(i) && !not_run.iter().any(|d| d.index == *i))
+            .collect();
+        // Workers pop from the back, so hand the programs out in the order they were asked for
+        queue.sort_by_key(|(i, _)| std::cmp::Reverse(indices.iter().position(|x| x == i)));
+        let workers = max_workers.clamp(1, queue.len().max(1));
+        let queue = std::sync::Mutex::new(queue);
+        let results = std::sync::Mutex::new(not_run);
+
+        thread::scope(|scope| {
+            for _ in 0..workers {
+                scope.spawn(|| loop {
+                    let next = queue.lock().unwrap().pop();
+                    let Some((index, program)) = next else { break };
+                    let buffer = BufferOutput::new();
+                    program.share_output(shared_output(HeldOutput { held: buffer.clone(), prompts: output.clone() }));
+                    let start = Instant::now();
+                    let result = program.run_with_args(&[]);
+                    let duration = start.elapsed();
+                    program.share_output(output.clone());
+
+                    let status = match result {
+                        Ok(()) => BatchStatus::Passed,
+                        Err(e) => BatchStatus::Failed(e),
+                    };
+                    let result = BatchResult { index, name: program.get_name(), status, duration };
+                    let style = set_color(Style::new(), program.get_color());
+                    let mut out = output.lock().unwrap();
+                    for line in block_lines(&result, &style, &buffer.lines()) {
+                        out.write_line(&line);
+                    }
+                    drop(out);
+                    results.lock().unwrap().push(result);
+                });
+            }
+        });
+
+        let mut results = results.into_inner().unwrap();
+        results.sort_by_key(|r| indices.iter().position(|i| *i == r.index));
+        self.write_line(self.style.apply_to("\nSummary"));
+        for line in summary_lines(&results) {
+            self.write_line(line);
+        }
+        results
     }
 
+    /// Whether a program in a batch should run. Disabled programs are passed over with their reason
+    /// and the rest are asked about if they need confirming, the batch goes on either way.
+    fn is_runnable(&self, index: usize) -> bool {
+        if let Some(reason) = self.programs[index].disabled_reason() {
+            self.write_line(Style::new().dim().apply_to(format!("{} skipped, disabled: {}", self.programs[index].name(), reason)));
+            return false;
+        }
+        match self.confirm_run(index) {
+            Ok(()) => true,
+            Err(e) => {
+                self.err(Some(&e.to_string()));
+                false
+            }
+        }
+    }
+
+    /// Prints what went wrong in a program run. Typing `quit` isn't a failure, `run` handles it.
+    fn report(&self, result: &Result<(), CliError>) {
+        match result {
+            Ok(()) | Err(CliError::Quit) => {}
+            Err(e) => self.err(Some(&e.to_string())),
+        }
+    }
+
+    /// Reports an index with no program behind it and records it as failed, the way `run_program` fails for it.
+    fn missing_program(&self, index: usize) -> BatchResult {
+        let error = CliError::ProgramNotFound(format!("Index {} out of bounds", index));
+        self.err(Some(&error.to_string()));
+        BatchResult { index, name: format!("#{}", index), status: BatchStatus::Failed(error), duration: Default::default() }
+    }
+
+    /// The number shown beside an entry in the menu.
+    fn entry_number(&self, index: usize) -> usize {
+        if self.one_based { index + 1 } else { index }
+    }
+
+    fn run_command(&mut self, index: usize, args: &[String]) -> MenuOutcome {
+        let name = self.commands[index].name().to_string();
+        let builtin = match &self.commands[index].action {
+            CommandAction::Builtin(b) => *b,
+            CommandAction::Custom(_) => {
+                // The handler gets the whole system, so the registry is moved out while it runs.
+                let mut commands = std::mem::take(&mut self.commands);
+                let result = match &mut commands[index].action {
+                    CommandAction::Custom(handler) => handler(self, args),
+                    CommandAction::Builtin(_) => Ok(()),
+                };
+                // Commands the handler registered replace the ones with the same name
+                commands.retain(|c| !self.commands.iter().any(|new| new.name() == c.name()));
+                commands.append(&mut self.commands);
+                self.commands = commands;
+                if let Err(e) = &result {
+                    self.err(Some(&e.to_string()));
+                }
+                return MenuOutcome::Command(name, result);
+            }
+        };
+
+        match builtin {
+            Builtin::Quit => MenuOutcome::Quit,
+            Builtin::Back => MenuOutcome::Back,
+            Builtin::Home => MenuOutcome::Home,
+            Builtin::Help => {
+                match args.first() {
+                    Some(word) => match self.resolve(word) {
+                        Selection::System(i) => self.systems[i].show_help(),
+                        _ => {
+                            if let Err(e) = self.show_program_help(word) {
+                                self.err(Some(&e.to_string()));
+                            }
+                        }
+                    },
+                    None => self.show_help(),
+                }
+                MenuOutcome::Help
+            }
+            Builtin::Bench => {
+                self.run_bench();
+                MenuOutcome::Bench
+            }
+            Builtin::Clear => {
+                self.write_line("\x1B[2J\x1B[1;1H");
+                MenuOutcome::Command(name, Ok(()))
+            }
+            Builtin::History => {
+                for (i, line) in self.history.iter().enumerate() {
+                    self.write_line(format!("{0: <5} {1}", i + 1, line));
+                }
+                MenuOutcome::Command(name, Ok(()))
+            }
+            Builtin::Doctor => {
+                let result = self.doctor();
+                if let Err(e) = &result {
+                    self.err(Some(&e.to_string()));
+                }
+                MenuOutcome::Command(name, result)
+            }
+            Builtin::Settings => {
+                let result = self.settings(args);
+                if let Err(e) = &result {
+                    self.err(Some(&e.to_string()));
+                }
+                MenuOutcome::Command(name, result)
+            }
+        }
+    }
+
+    /// Prints a table of every program's requirements and whether they're met, sub-systems included.
+    /// Fails with `CliError::CommandFailed` if any aren't, so it can gate scripts and CI.
+    pub fn doctor(&self) -> Result<(), CliError> {
+        self.write_line(format!("{0: <30} {1: <30} {2}", "Program", "Requirement", "Status"));
+        let missing = self.write_requirements("");
+        if missing == 0 {
+            self.write_line(Style::new().green().apply_to("All requirements met"));
+            Ok(())
+        } else {
+            Err(CliError::CommandFailed(format!("{} requirement(s) missing", missing)))
+        }
+    }
+
+    /// Writes the doctor rows for this system's programs, returning how many requirements are missing.
+    fn write_requirements(&self, prefix: &str) -> usize {
+        let mut missing = 0;
+        for program in &self.programs {
+            let name = format!("{}{}", prefix, program.name());
+            if program.requirements().is_empty() {
+                self.write_line(format!("{0: <30} {1: <30} {2}", name, "-", Style::new().dim().apply_to("none")));
+            }
+            for requirement in program.requirements() {
+                let status = if requirement.is_met() {
+                    Style::new().green().apply_to("ok")
+                } else {
+                    missing += 1;
+                    Style::new().red().apply_to("missing")
+                };
+                self.write_line(format!("{0: <30} {1: <30} {2}", name, requirement.to_string(), status));
+            }
+        }
+        for sys in &self.systems {
+            missing += sys.write_requirements(&format!("{}{}/", prefix, sys.name()));
+        }
+        missing
+    }
+
+    /// Shows the settings, or with a key and value changes them for this system and everything in it.
+    fn settings(&mut self, args: &[String]) -> Result<(), CliError> {
+        let (key, value) = match args {
+            [] => {
+                self.write_line(self.style.apply_to(format!("=== {} Settings ===", self.name)));
+                self.write_line(format!("  color   {}", self.color));
+                self.write_line(format!("  sleep   {}", self.sleep));
+                self.write_line(format!("  silent  {}", self.silent));
+                return Ok(());
+            }
+            [key, value] => (key.as_str(), value.as_str()),
+            _ => return Err(CliError::InvalidInput("Usage: settings [key value]".to_string())),
+        };
+        let invalid = |what: &str| CliError::InvalidInput(format!("{} is not a valid {}", value, what));
+        let (mut color, mut sleep, mut silent) = (self.color, self.sleep, self.silent);
+        match key {
+            "color" => color = value.parse().map_err(|_| invalid("color"))?,
+            "sleep" => sleep = value.parse().map_err(|_| invalid("sleep time"))?,
+            "silent" => silent = value.parse().map_err(|_| invalid("true/false"))?,
+            _ => return Err(CliError::InvalidInput(format!("Unknown setting {}", key))),
+        }
+        self.inherit(color, sleep, silent);
+        Ok(())
+    }
+
+    /// Adds a meta-command to the menu prompt, replacing any command with the same name.
+    pub fn register_command(&mut self, command: MetaCommand) {
+        self.commands.retain(|c| c.name() != command.name());
+        self.commands.push(command);
+    }
+
+    pub fn commands(&self) -> &[MetaCommand] {
+        &self.commands
+    }
+
+    /// Everything typed at this system's menu prompt, oldest first.
+    pub fn history(&self) -> &[String] {
+        &self.history
+    }
+
+    /// Runs and times every program, with their output sent to a `NullOutput` while they run.
+    /// Disabled programs are skipped, and programs that need confirming are asked about first.
     pub fn run_bench(&mut self) {
-        for p in &mut self.programs {
+        let muted = shared_output(NullOutput);
+        for i in 0..self.programs.len() {
+            if let Some(reason) = self.programs[i].disabled_reason() {
+                self.write_line(format!("{} ... skipped, disabled: {}", self.programs[i].name(), reason));
+                continue;
+            }
+            if self.confirm_run(i).is_err() {
+                self.write_line(format!("{} ... skipped, not confirmed", self.programs[i].name()));
+                continue;
+            }
+            let p = &mut self.programs[i];
             let prev_silent = p.get_silence();
             p.set_silence(true);
-            let mute = Gag::stdout().unwrap();
+            p.share_output(muted.clone());
             let start = SystemTime::now();
-            p.run();
-            let res = start.elapsed().unwrap().as_micros() as f64 / 1000.0;    
-            drop(mute);
+            let _ = p.run();
+            let res = start.elapsed().unwrap().as_micros() as f64 / 1000.0;
+            p.share_output(self.output.clone());
             p.set_silence(prev_silent);
-            println!("{} ... bench: \t{:.2} ms", p.name(), res);   
+            let line = format!("{} ... bench: \t{:.2} ms", p.name(), res);
+            self.write_line(line);
         }
     }
 
     pub fn print(&mut self, s: &str){
-        println!("{}", self.style.apply_to(self.name.to_string()+&"> ".to_string()+&s.to_string()));
+        self.write_line(self.style.apply_to(format!("{}> {}", self.name, s)));
         thread::sleep(time::Duration::from_millis(self.sleep));
     }
 
     pub fn add_program_with_inheritance<F>(&mut self, name: String, run_func: F)
     where
-        F: Fn() + 'static,
+        F: FnMut() + Send + 'static,
     {
         let program = Program::builder(name)
             .action(run_func)
@@ -142,23 +1113,60 @@ impl System {
             .sleep(self.sleep)
             .silent(self.silent)
             .build();
-        self.programs.push(program);
+        self.append_program(program);
     }
 
-    pub fn append_program(&mut self, prog: Program){
+    pub fn append_program(&mut self, mut prog: Program){
+        prog.share_input(self.input.clone());
+        prog.share_output(self.output.clone());
         self.programs.push(prog);
     }
 
+    /// Adds a sub-system which takes on this system's color, sleep and silence, as do its programs.
+    pub fn add_system_with_inheritance(&mut self, mut sys: System) {
+        sys.inherit(self.color, self.sleep, self.silent);
+        self.append_system(sys);
+    }
+
+    /// Adds a sub-system, it reads and writes through this system's input and output.
+    pub fn append_system(&mut self, sys: System) {
+        self.systems.push(sys);
+        self.share_io();
+    }
+
+    fn inherit(&mut self, color: TermColor, sleep: u64, silent: bool) {
+        self.set_color(color);
+        self.set_sleep(sleep);
+        self.set_silence(silent);
+        for p in &mut self.programs {
+            p.set_color(color);
+            p.set_sleep(sleep);
+            p.set_silence(silent);
+        }
+        for sys in &mut self.systems {
+            sys.inherit(color, sleep, silent);
+        }
+    }
+
     pub fn err(&self, s: Option<&String>) {
-        println!("{}", Style::new().red().apply_to(self.name.to_string()+
-                                                    &"Error> ".to_string()+
+        self.write_line(Style::new().red().apply_to(self.name.to_string()+
+                                                    "Error> "+
                                                     s.unwrap_or(&"Error".to_string())));
     }
 
-    pub fn programs_with_tag(&self, tag: &str) -> Vec<&Program> {
-        self.programs.iter()
-            .filter(|p| p.has_tag(tag))
-            .collect()
+    /// Programs matching a tag query, a plain tag or an expression like `(prod | staging) & readonly`.
+    pub fn programs_with_tag(&self, query: &str) -> Result<Vec<&Program>, CliError> {
+        Ok(self.tagged_indices(query)?.into_iter().map(|i| &self.programs[i]).collect())
+    }
+
+    /// Indices of the programs that show up in menus right now, see `Program::is_listed`.
+    fn listed(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.programs.len()).filter(|i| self.programs[*i].is_listed())
+    }
+
+    fn tagged_indices(&self, query: &str) -> Result<Vec<usize>, CliError> {
+        let expr = TagExpr::parse(query)?;
+        Ok(self.listed().filter(|i| expr.matches(self.programs[*i].tags())).collect())
     }
 
     pub fn all_tags(&self) -> Vec<String> {
@@ -170,59 +1178,152 @@ impl System {
         tags
     }
 
-    /// Cleanly shuts down the system
-    pub fn quit(&self) {
-        println!("\n\n\t Shutting Down\n\n");
-        thread::sleep(time::Duration::from_millis(200));
-        std::process::exit(0);
+    /// Every tag as a tree, `net/dns` and `net/http` end up below one `net` node.
+    pub fn tag_tree(&self) -> Vec<TagNode> {
+        tag_tree(self.programs.iter().flat_map(|p| p.tags().iter().map(String::as_str)))
+    }
+
+    /// Tears down every program that has been set up, here and in sub-systems. `run` does this when the user quits.
+    pub fn teardown(&mut self) {
+        for i in 0..self.programs.len() {
+            if let Err(e) = self.programs[i].teardown() {
+                self.err(Some(&e.to_string()));
+            }
+        }
+        for sys in &mut self.systems {
+            sys.teardown();
+        }
+    }
+
+    /// Cleanly shuts down the system, tearing down its programs before the process exits.
+    pub fn quit(&mut self) {
+        self.teardown();
+        shut_down(&self.output);
+    }
+
+    /// Prompts for a line from the system's input source.
+    /// Typing `quit` is a `CliError::Quit`, running out of input is a `CliError::EndOfInput`.
+    pub fn input(&self, label: &str) -> Result<String, CliError> {
+        self.prompter().input(label)
     }
 
-    pub fn input(&self, label: &str) -> String {
-        println!("{}", self.style.apply_to(label));
-        let s: String = read!("{}\n");
-        println!("{}", self.style.apply_to(format!("{}> {}", self.name, s)));
-        let s = s.replace('\r', "");
-        
-        if s.eq("quit") {
-            self.quit();
+    /// Prompts for a password or token. It isn't shown while typed on a terminal or echoed back afterwards,
+    /// and from then on it's replaced with `********` wherever it appears as a whole word in the system's output.
+    /// Secrets shorter than `MIN_REDACTED_LEN` characters are only kept out of the echo.
+    pub fn input_secret(&self, label: &str) -> Result<Secret, CliError> {
+        self.prompter().input_secret(label)
+    }
+
+    /// Prompts and reads a line without treating `quit` as a request to shut down.
+    fn read_input(&self, label: &str) -> Result<String, CliError> {
+        self.prompter().read_line(label)
+    }
+
+    /// The system's prompts, asked through its input and output.
+    fn prompter(&self) -> Prompter<'_> {
+        Prompter {
+            name: &self.name,
+            style: &self.style,
+            input: &self.input,
+            output: &self.output,
+            error_label: format!("{}Error> ", self.name),
         }
-        s
     }
 
-    pub fn input_with_validation<F>(&self, label: &str, validator: F) -> String 
+    /// Prompts until `validator` accepts the answer, showing why it didn't each time.
+    pub fn input_with_validation(&self, label: &str, validator: impl Validator) -> Result<String, CliError> {
+        self.prompter().input_with_validation(label, validator)
+    }
+
+    /// Asks a yes or no question, `[Y/n]` or `[y/N]` shows what an empty answer means.
+    pub fn confirm(&self, label: &str, default: bool) -> Result<bool, CliError> {
+        self.prompter().confirm(label, default)
+    }
+
+    /// Lists the items, numbered from 1, and asks for one by number or name. Returns its index.
+    pub fn select<T: std::fmt::Display>(&self, label: &str, items: &[T]) -> Result<usize, CliError> {
+        self.prompter().select(label, items)
+    }
+
+    /// Lists the items, numbered from 1, and asks for any number of them, e.g. `1,3`, `2-4` or names.
+    /// Returns their indices in the order given, an empty answer picks none.
+    pub fn multi_select<T: std::fmt::Display>(&self, label: &str, items: &[T]) -> Result<Vec<usize>, CliError> {
+        self.prompter().multi_select(label, items)
+    }
+
+    /// Gives `None` for anything that isn't a number, `input_parsed` asks again instead.
+    pub fn input_number(&self, label: &str) -> Result<Option<i32>, CliError> {
+        Ok(self.input(label)?.parse().ok())
+    }
+
+    /// Prompts until the answer parses as `T`, showing why it didn't each time.
+    pub fn input_parsed<T>(&self, label: &str) -> Result<T, CliError>
     where
-        F: Fn(&str) -> bool
+        T: std::str::FromStr,
+        T::Err: std::fmt::Display,
     {
-        loop {
-            let input = self.input(label);
-            if validator(&input) {
-                return input;
-            }
-            self.err(Some(&"Invalid input".to_string()));
-        }
+        self.prompt(Prompt::new(label))
     }
 
-    pub fn input_number(&self, label: &str) -> Option<i32> {
-        self.input(label).parse().ok()
+    /// Asks a typed question with a default, range or retry limit, see `Prompt`.
+    pub fn prompt<T>(&self, prompt: Prompt<T>) -> Result<T, CliError>
+    where
+        T: std::str::FromStr,
+        T::Err: std::fmt::Display,
+    {
+        self.prompter().prompt(prompt)
     }
 
-    pub fn run_program(&self, index: usize) -> Result<(), CliError> {
+    /// Runs a program, returning the result of its action. Programs that need confirming are asked about first.
+    pub fn run_program(&mut self, index: usize) -> Result<(), CliError> {
         if index >= self.programs.len() {
             return Err(CliError::ProgramNotFound(format!("Index {} out of bounds", index)));
         }
-        self.programs[index].run();
-        Ok(())
+        self.run_confirmed(index, &[])
     }
 
-    pub fn input_required(&self, label: &str) -> Result<String, CliError> {
-        let input = self.input(label);
-        if input.is_empty() {
-            Err(CliError::InvalidInput("Input cannot be empty".to_string()))
+    fn run_confirmed(&mut self, index: usize, args: &[String]) -> Result<(), CliError> {
+        self.confirm_run(index)?;
+        self.programs[index].run_with_args(args)
+    }
+
+    /// The warning to confirm before running a program, from the program itself or the system's confirm tags.
+    fn confirm_warning(&self, index: usize) -> Option<String> {
+        let program = &self.programs[index];
+        program.confirm_message().map(str::to_string).or_else(|| {
+            self.confirm_tags.iter()
+                .find(|tag| program.has_tag(tag))
+                .map(|tag| format!("{} is tagged {}", program.name(), tag))
+        })
+    }
+
+    /// Asks before running a program that needs confirming, declining is a `CliError::NotConfirmed`.
+    /// Disabled programs aren't asked about, running them fails anyway.
+    fn confirm_run(&self, index: usize) -> Result<(), CliError> {
+        let program = &self.programs[index];
+        let warning = match self.confirm_warning(index) {
+            Some(warning) if program.disabled_reason().is_none() => warning,
+            _ => return Ok(()),
+        };
+        self.write_line(Style::new().yellow().bold().apply_to(format!("Warning: {}", warning)));
+        let confirmed = match self.confirm_style {
+            ConfirmStyle::YesNo => {
+                let answer = self.read_input(&format!("Run {}? [y/N]", program.name()))?;
+                matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+            }
+            ConfirmStyle::TypeName => self.read_input(&format!("Type {} to confirm:", program.name()))?.trim() == program.name(),
+        };
+        if confirmed {
+            Ok(())
         } else {
-            Ok(input)
+            Err(CliError::NotConfirmed(program.get_name()))
         }
     }
 
+    pub fn input_required(&self, label: &str) -> Result<String, CliError> {
+        self.prompter().input_required(label)
+    }
+
     pub fn name(&self) -> &str {
         &self.name
     }
@@ -231,22 +1332,47 @@ impl System {
         &self.programs
     }
 
-    pub fn display(&self) {
-        println!("System Name: {}", self.name);
-        println!("Color: {:?}", self.color.to_string());
-        println!("Style: {:?}", self.style);
-        println!("Sleep: {}", self.sleep);
-        println!("Silent: {}", self.silent);
-        println!("Programs:");
+    pub fn systems(&self) -> &[System] {
+        &self.systems
+    }
 
+    /// Index of the first program with exactly this name, or failing that this alias.
+    pub fn find_program(&self, name: &str) -> Option<usize> {
+        self.programs.iter().position(|p| p.name() == name)
+            .or_else(|| self.programs.iter().position(|p| p.aliases().iter().any(|a| a == name)))
+    }
+
+    /// Index of the first sub-system with exactly this name.
+    pub fn find_system(&self, name: &str) -> Option<usize> {
+        self.systems.iter().position(|s| s.name() == name)
+    }
+
+    pub fn get_color(&self) -> TermColor {
+        self.color
+    }
+
+    pub fn display(&self) {
+        self.write_line(format!("System Name: {}", self.name));
+        self.write_line(format!("Color: {:?}", self.color.to_string()));
+        self.write_line(format!("Style: {:?}", self.style));
+        self.write_line(format!("Sleep: {}", self.sleep));
+        self.write_line(format!("Silent: {}", self.silent));
+        self.write_line("Programs:");
         for (index, program) in self.programs.iter().enumerate() {
-            println!("  Program {}:", index + 1);
-            println!("    Name: {}", program.get_name());
-            println!("    Color: {:?}", program.get_color());
-            println!("    Sleep: {}", program.get_sleep());
-            println!("    Silent: {}", program.get_silence());
-            println!("    Description: {}", program.get_description());
-            println!("    Tags: {:?}", program.get_tags());
+            self.write_line(format!("  Program {}:", index + 1));
+            self.write_line(format!("    Name: {}", program.get_name()));
+            self.write_line(format!("    Color: {:?}", program.get_color()));
+            self.write_line(format!("    Sleep: {}", program.get_sleep()));
+            self.write_line(format!("    Silent: {}", program.get_silence()));
+            self.write_line(format!("    Description: {}", program.get_description()));
+            self.write_line(format!("    Tags: {:?}", program.get_tags()));
+        }
+
+        if !self.systems.is_empty() {
+            self.write_line("Sub-systems:");
+            for sys in &self.systems {
+                self.write_line(format!("  {} ({} programs)", sys.name(), sys.programs().len()));
+            }
         }
     }
 
@@ -261,6 +1387,18 @@ impl SystemBuilder {
             sleep: 100,
             silent: false,
             programs: Vec::new(),
+            systems: Vec::new(),
+            input: shared_input(StdinInput),
+            output: shared_output(TerminalOutput),
+            commands: builtin_commands(),
+            one_based: false,
+            menu_mode: MenuMode::Numbered,
+            stop_on_failure: true,
+            workers: thread::available_parallelism().map(|n| n.get()).unwrap_or(4),
+            grouping: MenuGrouping::Flat,
+            order: MenuOrder::Added,
+            confirm_tags: Vec::new(),
+            confirm_style: ConfirmStyle::YesNo,
         }
     }
 
@@ -285,9 +1423,73 @@ impl SystemBuilder {
         self
     }
 
+    pub fn input_source(mut self, source: impl InputSource + Send + 'static) -> Self {
+        self.input = shared_input(source);
+        self
+    }
+
+    pub fn output_sink(mut self, sink: impl OutputSink + Send + 'static) -> Self {
+        self.output = shared_output(sink);
+        self
+    }
+
+    /// Numbers the menu from 1 instead of 0.
+    pub fn one_based(mut self, one_based: bool) -> Self {
+        self.one_based = one_based;
+        self
+    }
+
+    pub fn menu_mode(mut self, mode: MenuMode) -> Self {
+        self.menu_mode = mode;
+        self
+    }
+
+    /// Whether a batch stops at the first failing program, on by default.
+    pub fn stop_on_failure(mut self, stop: bool) -> Self {
+        self.stop_on_failure = stop;
+        self
+    }
+
+    /// How many programs a `&` batch at the menu prompt runs at once, the number of CPUs by default.
+    pub fn workers(mut self, workers: usize) -> Self {
+        self.workers = workers;
+        self
+    }
+
+    /// Lists the programs under headings instead of one flat list.
+    pub fn group_by(mut self, grouping: MenuGrouping) -> Self {
+        self.grouping = grouping;
+        self
+    }
+
+    /// Sorts the menu, a `/` search still lists the best matches first.
+    pub fn order_by(mut self, order: MenuOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Asks before running any program with this tag, e.g. `dangerous`, as if it had `ProgramBuilder::confirm`.
+    pub fn confirm_tag(mut self, tag: impl Into<String>) -> Self {
+        self.confirm_tags.push(tag.into());
+        self
+    }
+
+    /// How to ask before running a program that needs confirming, `[y/N]` by default.
+    pub fn confirm_style(mut self, style: ConfirmStyle) -> Self {
+        self.confirm_style = style;
+        self
+    }
+
+    /// Adds a meta-command to the menu prompt, replacing any command with the same name.
+    pub fn command(mut self, command: MetaCommand) -> Self {
+        self.commands.retain(|c| c.name() != command.name());
+        self.commands.push(command);
+        self
+    }
+
     pub fn add_program_with_inheritance<F>(mut self, name: String, run_func: F) -> Self
     where
-        F: Fn() + 'static,
+        F: FnMut() + Send + 'static,
     {
         let program = Program::builder(name)
             .action(run_func)
@@ -304,50 +1506,157 @@ impl SystemBuilder {
         self
     }
 
+    pub fn add_system_with_inheritance(mut self, mut sys: System) -> Self {
+        sys.inherit(self.color, self.sleep, self.silent);
+        self.systems.push(sys);
+        self
+    }
+
+    pub fn append_system(mut self, sys: System) -> Self {
+        self.systems.push(sys);
+        self
+    }
+
     pub fn build(self) -> System {
-        System {
+        let mut sys = System {
             name: self.name,
             color: self.color,
             style: set_color(Style::new(), self.color),
             sleep: self.sleep,
             silent: self.silent,
             programs: self.programs,
+            systems: self.systems,
+            input: self.input,
+            output: self.output,
+            commands: self.commands,
+            history: Vec::new(),
+            one_based: self.one_based,
+            filter: None,
+            menu_mode: self.menu_mode,
+            stop_on_failure: self.stop_on_failure,
+            workers: self.workers,
+            grouping: self.grouping,
+            order: self.order,
+            confirm_tags: self.confirm_tags,
+            confirm_style: self.confirm_style,
+        };
+        sys.share_io();
+        sys
+    }
+}
+
+/// Writes the shutdown message and exits the process.
+pub(crate) fn shut_down(output: &SharedOutput) -> ! {
+    output.lock().unwrap().write_line("\n\n\t Shutting Down\n\n");
+    thread::sleep(time::Duration::from_millis(200));
+    std::process::exit(0);
+}
+
+/// Prints the given entries, indices past the programs refer to sub-systems.
+/// Programs are sorted by the system's order unless a `/` search ranked them.
+fn print_menu(sys: &mut System, indices: Vec<usize>, label: &str) -> MenuOutcome {
+    let (mut programs, systems): (Vec<usize>, Vec<usize>) = indices.into_iter().partition(|i| *i < sys.programs.len());
+    if sys.filter.is_none() {
+        sort_programs(sys, &mut programs);
+    }
+    let grouping = match (sys.grouping, sys.order) {
+        (MenuGrouping::Flat, MenuOrder::Category) => MenuGrouping::Category,
+        (grouping, _) => grouping,
+    };
+    let groups = match grouping {
+        MenuGrouping::Flat => vec![(String::new(), programs)],
+        MenuGrouping::Tag => group_programs(sys, &programs, "untagged",
+            |p| p.tags().iter().map(|t| top_level(t).to_string()).collect()),
+        MenuGrouping::Category => group_programs(sys, &programs, "other",
+            |p| p.category().map(str::to_string).into_iter().collect()),
+    };
+    for (heading, members) in groups {
+        if grouping != MenuGrouping::Flat {
+            sys.write_line(sys.style.clone().bold().apply_to(format!("[{}]", heading)));
+        }
+        for i in members {
+            print_entry(sys, i);
+        }
+    }
+    for i in systems {
+        print_entry(sys, i);
+    }
+
+    match sys.read_input(label) {
+        Ok(input) => sys.handle_menu_input(&input),
+        // Closing the input, e.g. Ctrl-D, leaves the menu like `quit` does.
+        Err(CliError::EndOfInput) => MenuOutcome::Quit,
+        Err(e) => {
+            sys.err(Some(&e.to_string()));
+            MenuOutcome::Cancelled
         }
     }
 }
 
-fn print_menu(sys: &mut System, indices: Vec<usize>) -> Option<usize>{
-    for i in indices {
-        println!("{0: <5} {1: <30} {2}",
-            sys.style.apply_to(format!("{})", i)),
-            sys.style.apply_to(sys.programs[i].name()),
-            sys.programs[i].description());
-        
-        if !sys.programs[i].tags().is_empty() {
-            println!("     Tags: {}",Style::new().italic().apply_to(sys.programs[i].tags().join(", ")));
-        }
-    }
-
-    let input = sys.input("Pick program to launch:");
-    if input == "bench" {
-        sys.run_bench();
-        return None;
-    } else {
-        let res = input.parse::<usize>();
-        let prog = match res {
-            Ok(x) => x,
-            Err(_) => {
-                println!("invalid input");
-                return None;
+fn print_entry(sys: &System, i: usize) {
+    if i >= sys.programs.len() {
+        let sub = &sys.systems[i - sys.programs.len()];
+        sys.write_line(format!("{0: <5} {1: <30} {2}",
+            sys.style.apply_to(format!("{})", sys.entry_number(i))),
+            sys.style.apply_to(format!("{} >", sub.name())),
+            Style::new().italic().apply_to("sub-system")));
+        return;
+    }
+
+    if let Some(reason) = sys.programs[i].disabled_reason() {
+        let grey = Style::new().dim();
+        sys.write_line(format!("{0: <5} {1: <30} {2}",
+            grey.apply_to(format!("{})", sys.entry_number(i))),
+            grey.apply_to(sys.programs[i].name()),
+            grey.clone().italic().apply_to(format!("disabled: {}", reason))));
+        return;
+    }
+
+    sys.write_line(format!("{0: <5} {1: <30} {2}",
+        sys.style.apply_to(format!("{})", sys.entry_number(i))),
+        sys.style.apply_to(sys.programs[i].name()),
+        sys.programs[i].description()));
+
+    if !sys.programs[i].tags().is_empty() {
+        sys.write_line(format!("     Tags: {}",Style::new().italic().apply_to(sys.programs[i].tags().join(", "))));
+    }
+}
+
+fn sort_programs(sys: &System, programs: &mut [usize]) {
+    let p = |i: &usize| &sys.programs[*i];
+    match sys.order {
+        MenuOrder::Added => programs.sort(),
+        MenuOrder::Category => programs.sort_by_key(|i| (p(i).category().is_none(), p(i).category(), -p(i).priority(), *i)),
+        MenuOrder::Priority => programs.sort_by_key(|i| (-p(i).priority(), *i)),
+        MenuOrder::Name => programs.sort_by_key(|i| (p(i).name().to_lowercase(), *i)),
+        MenuOrder::RecentlyUsed => programs.sort_by_key(|i| (std::cmp::Reverse(p(i).last_run()), *i)),
+    }
+}
+
+/// The programs under each heading, keeping their order. Headings are sorted by name with `fallback`,
+/// for programs without a heading, last. A program with several headings is listed under each.
+fn group_programs<F>(sys: &System, programs: &[usize], fallback: &str, headings: F) -> Vec<(String, Vec<usize>)>
+where
+    F: Fn(&Program) -> Vec<String>,
+{
+    let mut groups: Vec<(String, Vec<usize>)> = Vec::new();
+    let mut rest: Vec<usize> = Vec::new();
+    for &i in programs {
+        let headings = headings(&sys.programs[i]);
+        if headings.is_empty() {
+            rest.push(i);
+        }
+        for heading in headings {
+            match groups.iter_mut().find(|(h, _)| *h == heading) {
+                Some((_, members)) if !members.contains(&i) => members.push(i),
+                Some(_) => {}
+                None => groups.push((heading, vec![i])),
             }
-        };
-        
-        if prog >= sys.programs.len() {
-            println!("invalid input");
-            return None;
-        } else {
-            sys.programs[prog].run();
-            return Some(prog);
         }
     }
-}
\ No newline at end of file
+    groups.sort_by(|a, b| a.0.cmp(&b.0));
+    if !rest.is_empty() {
+        groups.push((fallback.to_string(), rest));
+    }
+    groups
+}
@@ -0,0 +1,190 @@
+//--------------------- Tags --------------------------------------------------
+// path src\cli\tags.rs
+// A small query language over program tags, e.g. `db & !slow`, `net | dns` or `(prod | staging) & readonly`.
+// `!` binds tightest, then `&`, then `|`. A plain tag is a query too, matching the programs that have it.
+// Tags can be nested with `/`, like `net/dns`, and a tag matches everything below it, so `net` matches `net/dns`.
+use std::fmt;
+use std::str::FromStr;
+
+use crate::cli::system::CliError;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TagExpr {
+    Tag(String),
+    Not(Box<TagExpr>),
+    And(Box<TagExpr>, Box<TagExpr>),
+    Or(Box<TagExpr>, Box<TagExpr>),
+}
+
+impl TagExpr {
+    /// Parses a query, a mistake is reported as `CliError::InvalidInput` saying where it is.
+    pub fn parse(query: &str) -> Result<Self, CliError> {
+        let mut parser = Parser { chars: query.chars().collect(), pos: 0 };
+        let expr = parser.or()?;
+        parser.skip_whitespace();
+        match parser.peek() {
+            None => Ok(expr),
+            Some(c) => Err(parser.error(&format!("unexpected '{}'", c))),
+        }
+    }
+
+    /// Whether a program with these tags matches the query.
+    pub fn matches(&self, tags: &[String]) -> bool {
+        match self {
+            TagExpr::Tag(tag) => tags.iter().any(|t| tag_matches(t, tag)),
+            TagExpr::Not(expr) => !expr.matches(tags),
+            TagExpr::And(a, b) => a.matches(tags) && b.matches(tags),
+            TagExpr::Or(a, b) => a.matches(tags) || b.matches(tags),
+        }
+    }
+}
+
+impl FromStr for TagExpr {
+    type Err = CliError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        TagExpr::parse(s)
+    }
+}
+
+impl fmt::Display for TagExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TagExpr::Tag(tag) => write!(f, "{}", tag),
+            TagExpr::Not(expr) => write!(f, "!{}", expr),
+            TagExpr::And(a, b) => write!(f, "({} & {})", a, b),
+            TagExpr::Or(a, b) => write!(f, "({} | {})", a, b),
+        }
+    }
+}
+
+/// Whether a program's tag is `query` or nested below it.
+pub fn tag_matches(tag: &str, query: &str) -> bool {
+    tag.strip_prefix(query).is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// The first segment of a tag, `net` for `net/dns`.
+pub fn top_level(tag: &str) -> &str {
+    tag.split('/').next().unwrap_or(tag)
+}
+
+/// A tag in the tree from `System::tag_tree`, holding the tags nested below it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TagNode {
+    /// The last segment, `dns` for `net/dns`.
+    pub name: String,
+    /// The whole tag, `net/dns`.
+    pub path: String,
+    pub children: Vec<TagNode>,
+}
+
+/// Builds a tree out of `/` separated tags, sorted by name. Parents are added even if no program has them on their own.
+pub fn tag_tree<'a>(tags: impl IntoIterator<Item = &'a str>) -> Vec<TagNode> {
+    let mut roots: Vec<TagNode> = Vec::new();
+    for tag in tags {
+        let mut level = &mut roots;
+        let mut path = String::new();
+        for segment in tag.split('/').filter(|s| !s.is_empty()) {
+            if !path.is_empty() {
+                path.push('/');
+            }
+            path.push_str(segment);
+            let position = match level.iter().position(|n| n.name == segment) {
+                Some(position) => position,
+                None => {
+                    level.push(TagNode { name: segment.to_string(), path: path.clone(), children: Vec::new() });
+                    level.len() - 1
+                }
+            };
+            level = &mut level[position].children;
+        }
+    }
+    sort_tree(&mut roots);
+    roots
+}
+
+fn sort_tree(nodes: &mut [TagNode]) {
+    nodes.sort_by(|a, b| a.name.cmp(&b.name));
+    for node in nodes {
+        sort_tree(&mut node.children);
+    }
+}
+
+fn is_tag_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '-' | '_' | '.' | ':' | '/')
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    /// Skips whitespace and consumes `c` if it's next.
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn error(&self, message: &str) -> CliError {
+        let query: String = self.chars.iter().collect();
+        CliError::InvalidInput(format!("{} at position {} in tag query '{}'", message, self.pos + 1, query))
+    }
+
+    fn or(&mut self) -> Result<TagExpr, CliError> {
+        let mut expr = self.and()?;
+        while self.eat('|') {
+            expr = TagExpr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<TagExpr, CliError> {
+        let mut expr = self.unary()?;
+        while self.eat('&') {
+            expr = TagExpr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<TagExpr, CliError> {
+        if self.eat('!') {
+            return Ok(TagExpr::Not(Box::new(self.unary()?)));
+        }
+        if self.eat('(') {
+            let expr = self.or()?;
+            if !self.eat(')') {
+                return Err(self.error("missing ')'"));
+            }
+            return Ok(expr);
+        }
+
+        self.skip_whitespace();
+        let start = self.pos;
+        while self.peek().is_some_and(is_tag_char) {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            return Err(match self.peek() {
+                Some(c) => self.error(&format!("expected a tag but found '{}'", c)),
+                None => self.error("expected a tag"),
+            });
+        }
+        Ok(TagExpr::Tag(self.chars[start..self.pos].iter().collect()))
+    }
+}
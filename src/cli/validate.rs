@@ -0,0 +1,189 @@
+//--------------------- Validate --------------------------------------------------
+// path src\cli\validate.rs
+// Checks for `input_with_validation`. A validator says why an answer isn't accepted, and the prompt
+// shows that reason before asking again. Built-in ones can be combined with `and`, `or` and `message`,
+// and any `Fn(&str) -> bool` is a validator that answers with "Invalid input".
+use std::fmt::Display;
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::str::FromStr;
+
+use regex::Regex;
+
+use crate::cli::system::CliError;
+
+pub trait Validator {
+    /// `Err` holds why the answer isn't accepted.
+    fn validate(&self, input: &str) -> Result<(), String>;
+
+    /// Accepts answers that pass both, the first failure is the reason given.
+    fn and<V: Validator>(self, other: V) -> And<Self, V>
+    where
+        Self: Sized,
+    {
+        And(self, other)
+    }
+
+    /// Accepts answers that pass either, the second reason is given when both fail.
+    fn or<V: Validator>(self, other: V) -> Or<Self, V>
+    where
+        Self: Sized,
+    {
+        Or(self, other)
+    }
+
+    /// Gives `message` as the reason instead of the validator's own.
+    fn message(self, message: impl Into<String>) -> Message<Self>
+    where
+        Self: Sized,
+    {
+        Message(self, message.into())
+    }
+}
+
+impl<F> Validator for F
+where
+    F: Fn(&str) -> bool,
+{
+    fn validate(&self, input: &str) -> Result<(), String> {
+        if self(input) { Ok(()) } else { Err("Invalid input".to_string()) }
+    }
+}
+
+pub struct And<A, B>(A, B);
+
+impl<A: Validator, B: Validator> Validator for And<A, B> {
+    fn validate(&self, input: &str) -> Result<(), String> {
+        self.0.validate(input)?;
+        self.1.validate(input)
+    }
+}
+
+pub struct Or<A, B>(A, B);
+
+impl<A: Validator, B: Validator> Validator for Or<A, B> {
+    fn validate(&self, input: &str) -> Result<(), String> {
+        self.0.validate(input).or_else(|_| self.1.validate(input))
+    }
+}
+
+pub struct Message<V>(V, String);
+
+impl<V: Validator> Validator for Message<V> {
+    fn validate(&self, input: &str) -> Result<(), String> {
+        self.0.validate(input).map_err(|_| self.1.clone())
+    }
+}
+
+/// A validator built from a function that gives its own reasons, see `custom`.
+pub struct Custom<F>(F);
+
+impl<F: Fn(&str) -> Result<(), String>> Validator for Custom<F> {
+    fn validate(&self, input: &str) -> Result<(), String> {
+        (self.0)(input)
+    }
+}
+
+/// Wraps a check that says why it failed, like `|s| if s.contains(' ') { Err("no spaces".into()) } else { Ok(()) }`.
+pub fn custom<F: Fn(&str) -> Result<(), String>>(check: F) -> Custom<F> {
+    Custom(check)
+}
+
+/// Rejects answers that are empty or only whitespace.
+pub fn non_empty() -> impl Validator {
+    custom(|input| if input.trim().is_empty() { Err("Can't be empty".to_string()) } else { Ok(()) })
+}
+
+/// Accepts answers the regular expression matches somewhere in, use `^` and `$` to match all of it.
+/// A pattern that doesn't compile is a `CliError::InvalidInput`.
+pub fn regex(pattern: &str) -> Result<impl Validator, CliError> {
+    let regex = Regex::new(pattern)
+        .map_err(|e| CliError::InvalidInput(format!("'{}' is not a valid pattern, {}", pattern, e)))?;
+    Ok(custom(move |input| {
+        if regex.is_match(input) {
+            Ok(())
+        } else {
+            Err(format!("'{}' doesn't match {}", input, regex.as_str()))
+        }
+    }))
+}
+
+/// Accepts answers with this many characters, both ends included.
+pub fn length(range: RangeInclusive<usize>) -> impl Validator {
+    custom(move |input| {
+        let count = input.chars().count();
+        if range.contains(&count) {
+            Ok(())
+        } else {
+            Err(format!("{} characters is not between {} and {}", count, range.start(), range.end()))
+        }
+    })
+}
+
+/// Accepts answers that parse as a `T` within the range, both ends included.
+pub fn number<T>(range: RangeInclusive<T>) -> impl Validator
+where
+    T: FromStr + PartialOrd + Display,
+    T::Err: Display,
+{
+    custom(move |input| {
+        let value: T = input.trim().parse().map_err(|e| format!("'{}' is not valid, {}", input.trim(), e))?;
+        if range.contains(&value) {
+            Ok(())
+        } else {
+            Err(format!("{} is not between {} and {}", value, range.start(), range.end()))
+        }
+    })
+}
+
+/// Accepts exactly one of the options.
+pub fn one_of<S: Into<String>>(options: impl IntoIterator<Item = S>) -> impl Validator {
+    let options: Vec<String> = options.into_iter().map(Into::into).collect();
+    custom(move |input| {
+        if options.iter().any(|option| option == input) {
+            Ok(())
+        } else {
+            Err(format!("'{}' is not one of {}", input, options.join(", ")))
+        }
+    })
+}
+
+/// Accepts paths to a file or directory that exists.
+pub fn path_exists() -> impl Validator {
+    custom(|input| if Path::new(input).exists() { Ok(()) } else { Err(format!("'{}' doesn't exist", input)) })
+}
+
+/// Accepts paths to a directory that exists.
+pub fn is_dir() -> impl Validator {
+    custom(|input| if Path::new(input).is_dir() { Ok(()) } else { Err(format!("'{}' is not a directory", input)) })
+}
+
+/// Accepts answers shaped like `name@example.com`. Only the syntax is checked.
+pub fn email() -> impl Validator {
+    custom(|input| {
+        let valid = !input.contains(char::is_whitespace)
+            && input.split_once('@').is_some_and(|(local, domain)| {
+                !local.is_empty() && !domain.contains('@') && is_domain(domain)
+            });
+        if valid { Ok(()) } else { Err(format!("'{}' is not an email address", input)) }
+    })
+}
+
+/// Accepts answers shaped like `https://example.com/path`, any scheme. Only the syntax is checked.
+pub fn url() -> impl Validator {
+    custom(|input| {
+        let valid = !input.contains(char::is_whitespace)
+            && input.split_once("://").is_some_and(|(scheme, rest)| {
+                scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                    && scheme.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+                    && !rest.split(['/', '?', '#']).next().unwrap_or("").is_empty()
+            });
+        if valid { Ok(()) } else { Err(format!("'{}' is not a URL", input)) }
+    })
+}
+
+/// At least two labels separated by dots, none of them empty.
+fn is_domain(domain: &str) -> bool {
+    let labels: Vec<&str> = domain.split('.').collect();
+    labels.len() > 1 && labels.iter().all(|label| !label.is_empty())
+}
@@ -26,12 +26,20 @@
 /// 
 /// system.add_program_with_inheritance("hello".to_string(), || println!("Hello, world!"));
 /// ```
-
 pub mod cli;
-pub use cli::{System, Program};
+pub use cli::{System, Program, Context, Runnable};
+pub use cli::args::{Arg, ArgType, Flag, ParsedArgs};
+pub use cli::commands::MetaCommand;
+pub use cli::batch::{BatchResult, BatchStatus};
+pub use cli::tags::{TagExpr, TagNode};
+pub use cli::requirements::Requirement;
+pub use cli::prompt::Prompt;
+pub use cli::validate::Validator;
+pub use cli::system::{CliError, ConfirmStyle, MenuOutcome, MenuMode, MenuGrouping, MenuOrder, Selection};
 pub use cli::program::ShellCommand;
+pub use cli::input::{InputSource, ScriptedInput, FileInput, StdinInput, Secret};
+pub use cli::output::{OutputSink, BufferOutput, FileOutput, NullOutput, TerminalOutput};
 
-#[macro_use] extern crate text_io;
 use std::{thread, time};
 use std::process::exit;
 use std::fmt;
@@ -66,6 +74,25 @@ impl fmt::Display for TermColor {
     }
 }
 
+impl std::str::FromStr for TermColor {
+    type Err = String;
+
+    /// Parses a color name, ignoring case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "black" => Ok(TermColor::Black),
+            "red" => Ok(TermColor::Red),
+            "green" => Ok(TermColor::Green),
+            "yellow" => Ok(TermColor::Yellow),
+            "blue" => Ok(TermColor::Blue),
+            "magenta" => Ok(TermColor::Magenta),
+            "cyan" => Ok(TermColor::Cyan),
+            "white" => Ok(TermColor::White),
+            _ => Err(format!("Unknown color {}", s)),
+        }
+    }
+}
+
 fn set_color(style: Style, color: TermColor) -> Style{
     match color {
         TermColor::Black => style.black(),
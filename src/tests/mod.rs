@@ -1,7 +1,14 @@
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests {
-    use crate::{System, Program, TermColor};
-    use crate::cli::system::CliError;
+    use crate::{System, Program, TermColor, ScriptedInput, FileInput, BufferOutput, FileOutput};
+    use crate::{Context, Runnable, Arg, ArgType, Flag, ParsedArgs, MetaCommand, BatchStatus, TagExpr, Requirement, Prompt, Validator};
+    use crate::cli::args::split_args;
+    use crate::cli::fuzzy::{fuzzy_score, edit_distance, suggestions};
+    use crate::cli::picker::{Picker, PickerAction, PickerEntry};
+    use crate::cli::program::ProgramBuilder;
+    use crate::cli::validate;
+    use crate::cli::system::{CliError, ConfirmStyle, MenuGrouping, MenuMode, MenuOrder, MenuOutcome, Selection};
     use std::io::{self, Write};
     use std::sync::{Mutex, Arc};
     use std::sync::atomic::{AtomicBool, Ordering};
@@ -82,7 +89,7 @@ mod tests {
         system.append_program(prog1);
         system.append_program(prog2);
 
-        let tagged = system.programs_with_tag("test");
+        let tagged = system.programs_with_tag("test").unwrap();
         assert_eq!(tagged.len(), 2);
 
         let all_tags = system.all_tags();
@@ -93,7 +100,7 @@ mod tests {
 
     #[test]
     fn test_error_handling() {
-        let system = System::builder("Test System")
+        let mut system = System::builder("Test System")
             .use_defaults()
             .build();
 
@@ -123,14 +130,14 @@ mod tests {
         let executed = Arc::new(AtomicBool::new(false));
         let executed_clone = executed.clone();
         
-        let program = Program::builder("Test Program")
+        let mut program = Program::builder("Test Program")
             .use_defaults()
             .action(move || {
                 executed_clone.store(true, Ordering::SeqCst);
             })
             .build();
 
-        program.run();
+        assert!(program.run().is_ok());
         assert!(executed.load(Ordering::SeqCst));
     }
 
@@ -196,7 +203,7 @@ mod tests {
             .build();
 
         assert_eq!(system.programs().len(), 0);
-        let tagged = system.programs_with_tag("test");
+        let tagged = system.programs_with_tag("test").unwrap();
         assert_eq!(tagged.len(), 0);
         let all_tags = system.all_tags();
         assert_eq!(all_tags.len(), 0);
@@ -242,6 +249,1478 @@ mod tests {
 
         assert_eq!(system.programs().len(), 2);
     }
+
+    #[test]
+    fn test_menu_input_outcomes() {
+        let executed = Arc::new(AtomicBool::new(false));
+        let executed_clone = executed.clone();
+
+        let mut system = System::builder("Test System")
+            .use_defaults()
+            .sleep(0)
+            .build();
+
+        let program = Program::builder("test")
+            .use_defaults()
+            .action(move || executed_clone.store(true, Ordering::SeqCst))
+            .build();
+        system.append_program(program);
+
+        assert!(matches!(system.handle_menu_input("0"), MenuOutcome::Ran(0, Ok(()))));
+        assert!(executed.load(Ordering::SeqCst));
+        assert!(matches!(system.handle_menu_input("quit"), MenuOutcome::Quit));
+        assert!(matches!(system.handle_menu_input(""), MenuOutcome::Cancelled));
+        assert!(matches!(system.handle_menu_input("7"), MenuOutcome::InvalidInput(s) if s == "7"));
+        assert!(matches!(system.handle_menu_input("abc"), MenuOutcome::InvalidInput(s) if s == "abc"));
+    }
+
+    #[test]
+    fn test_sub_system_navigation() {
+        let database = System::builder("Database")
+            .use_defaults()
+            .add_program_with_inheritance("migrate".to_string(), || {})
+            .build();
+
+        let mut system = System::builder("Ops")
+            .use_defaults()
+            .sleep(0)
+            .add_program_with_inheritance("status".to_string(), || {})
+            .append_system(database)
+            .build();
+
+        assert_eq!(system.systems().len(), 1);
+        assert!(matches!(system.handle_menu_input("1"), MenuOutcome::Entered(0)));
+        assert!(matches!(system.handle_menu_input("2"), MenuOutcome::InvalidInput(_)));
+        assert!(matches!(system.handle_menu_input("back"), MenuOutcome::Back));
+        assert!(matches!(system.handle_menu_input("home"), MenuOutcome::Home));
+    }
+
+    #[test]
+    fn test_sub_system_inheritance() {
+        let mut database = System::builder("Database")
+            .use_defaults()
+            .build();
+        database.append_program(Program::builder("migrate").use_defaults().action(|| {}).build());
+        database.append_system(System::builder("Backups").use_defaults().build());
+
+        let mut system = System::builder("Ops")
+            .use_defaults()
+            .color(TermColor::Blue)
+            .sleep(200)
+            .silent(true)
+            .build();
+        system.add_system_with_inheritance(database);
+
+        let database = &system.systems()[0];
+        assert_eq!(database.get_sleep(), 200);
+        assert!(database.get_silence());
+        assert!(matches!(database.get_color(), TermColor::Blue));
+        assert_eq!(database.programs()[0].get_sleep(), 200);
+        assert!(database.programs()[0].get_silence());
+        assert_eq!(database.systems()[0].get_sleep(), 200);
+    }
+
+    #[test]
+    fn test_run_loop_with_scripted_input() {
+        let runs = Arc::new(Mutex::new(0));
+        let runs_clone = runs.clone();
+
+        let mut system = System::builder("Test System")
+            .use_defaults()
+            .sleep(0)
+            .input_source(ScriptedInput::new(["0", "", "0", "quit"]))
+            .build();
+        system.add_program_with_inheritance("count".to_string(), move || {
+            *runs_clone.lock().unwrap() += 1;
+        });
+
+        assert!(matches!(system.run(), MenuOutcome::Quit));
+        assert_eq!(*runs.lock().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_end_of_input() {
+        let mut system = System::builder("Test System")
+            .use_defaults()
+            .input_source(ScriptedInput::new(["first"]))
+            .build();
+
+        assert_eq!(system.input("Label").unwrap(), "first");
+        assert!(matches!(system.input("Label"), Err(CliError::EndOfInput)));
+        // Running out of input at the menu ends the loop instead of panicking
+        assert!(matches!(system.run(), MenuOutcome::Quit));
+    }
+
+    #[test]
+    fn test_input_validation_and_numbers() {
+        let system = System::builder("Test System")
+            .use_defaults()
+            .input_source(ScriptedInput::new(["", "value", "12", "twelve"]))
+            .build();
+
+        assert_eq!(system.input_with_validation("Label", |s: &str| !s.is_empty()).unwrap(), "value");
+        assert_eq!(system.input_number("Number").unwrap(), Some(12));
+        assert_eq!(system.input_number("Number").unwrap(), None);
+        assert!(matches!(system.input_required("Label"), Err(CliError::EndOfInput)));
+    }
+
+    #[test]
+    fn test_file_input() {
+        let path = std::env::temp_dir().join(format!("cli_toolbox_input_{}.txt", std::process::id()));
+        std::fs::write(&path, "one\r\ntwo\n").unwrap();
+
+        let mut system = System::builder("Test System")
+            .use_defaults()
+            .build();
+        system.set_input_source(FileInput::open(&path).unwrap());
+
+        assert_eq!(system.input("Label").unwrap(), "one");
+        assert_eq!(system.input("Label").unwrap(), "two");
+        assert!(matches!(system.input("Label"), Err(CliError::EndOfInput)));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_output_is_captured() {
+        let buffer = BufferOutput::new();
+        let mut system = System::builder("Test System")
+            .use_defaults()
+            .sleep(0)
+            .output_sink(buffer.clone())
+            .build();
+        system.add_program_with_inheritance("test".to_string(), || {});
+
+        system.print("hello");
+        system.err(Some(&"oops".to_string()));
+        system.programs()[0].print("from program");
+        system.show_help();
+
+        let output = console::strip_ansi_codes(&buffer.contents()).to_string();
+        assert!(output.contains("Test System> hello"));
+        assert!(output.contains("Test SystemError> oops"));
+        assert!(output.contains("test> from program"));
+        assert!(output.contains("=== Test System Help ==="));
+    }
+
+    #[test]
+    fn test_sub_system_shares_output() {
+        let buffer = BufferOutput::new();
+        let child = System::builder("Child")
+            .use_defaults()
+            .sleep(0)
+            .add_program_with_inheritance("nested".to_string(), || {})
+            .build();
+        let system = System::builder("Parent")
+            .use_defaults()
+            .append_system(child)
+            .output_sink(buffer.clone())
+            .build();
+
+        system.systems()[0].programs()[0].print("deep");
+        assert!(buffer.contents().contains("nested> deep"));
+    }
+
+    #[test]
+    fn test_bench_mutes_programs() {
+        let buffer = BufferOutput::new();
+        let mut system = System::builder("Test System")
+            .use_defaults()
+            .sleep(0)
+            .output_sink(buffer.clone())
+            .build();
+        system.add_program_with_inheritance("noisy".to_string(), || {});
+
+        system.run_bench();
+        let lines = buffer.lines();
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].starts_with("noisy ... bench:"));
+
+        // The program writes to the system's sink again afterwards
+        system.programs()[0].print("after");
+        assert!(buffer.contents().contains("noisy> after"));
+    }
+
+    #[test]
+    fn test_file_output() {
+        let path = std::env::temp_dir().join(format!("cli_toolbox_output_{}.txt", std::process::id()));
+        let mut system = System::builder("Test System")
+            .use_defaults()
+            .sleep(0)
+            .build();
+        system.set_output_sink(FileOutput::create(&path).unwrap());
+        system.print("to file");
+        drop(system);
+
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert!(console::strip_ansi_codes(&written).contains("Test System> to file"));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_context_action() {
+        let buffer = BufferOutput::new();
+        let mut system = System::builder("Test System")
+            .use_defaults()
+            .sleep(0)
+            .input_source(ScriptedInput::new(["0", "Alice", "quit"]))
+            .output_sink(buffer.clone())
+            .build();
+
+        let program = Program::builder("greet")
+            .use_defaults()
+            .description("Greets someone")
+            .tag("example")
+            .action_with_context(|ctx: &mut Context| {
+                assert_eq!(ctx.name(), "greet");
+                assert_eq!(ctx.description(), "Greets someone");
+                assert_eq!(ctx.tags(), ["example".to_string()]);
+                let name = ctx.input("Who?")?;
+                ctx.print(&format!("Hello {}!", name));
+                Ok(())
+            })
+            .build();
+        system.append_program(program);
+
+        assert!(matches!(system.run(), MenuOutcome::Quit));
+        assert!(buffer.contents().contains("greet> Hello Alice!"));
+    }
+
+    #[test]
+    fn test_context_action_result() {
+        let mut system = System::builder("Test System")
+            .use_defaults()
+            .sleep(0)
+            .output_sink(BufferOutput::new())
+            .build();
+
+        let program = Program::builder("fails")
+            .use_defaults()
+            .action_with_context(|ctx| Err(CliError::CommandFailed(format!("{} broke", ctx.name()))))
+            .build();
+        system.append_program(program);
+
+        assert!(matches!(system.run_program(0), Err(CliError::CommandFailed(msg)) if msg == "fails broke"));
+        assert!(matches!(system.handle_menu_input("0"), MenuOutcome::Ran(0, Err(CliError::CommandFailed(_)))));
+    }
+
+    #[test]
+    fn test_context_args() {
+        let mut program = Program::builder("echo")
+            .use_defaults()
+            .silent(true)
+            .action_with_context(|ctx| {
+                assert_eq!(ctx.args().len(), 2);
+                assert_eq!(ctx.arg(0), Some("one"));
+                assert_eq!(ctx.arg(2), None);
+                Ok(())
+            })
+            .build();
+
+        assert!(program.run_with_args(&["one".to_string(), "two".to_string()]).is_ok());
+    }
+
+    struct Counter {
+        runs: usize,
+        log: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Runnable for Counter {
+        fn run(&mut self, ctx: &mut Context) -> Result<(), CliError> {
+            self.runs += 1;
+            self.log.lock().unwrap().push(format!("{} run {}", ctx.name(), self.runs));
+            Ok(())
+        }
+
+        fn setup(&mut self, _ctx: &mut Context) -> Result<(), CliError> {
+            self.log.lock().unwrap().push("setup".to_string());
+            Ok(())
+        }
+
+        fn teardown(&mut self, _ctx: &mut Context) -> Result<(), CliError> {
+            self.log.lock().unwrap().push("teardown".to_string());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_runnable_keeps_state() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut system = System::builder("Test System")
+            .use_defaults()
+            .sleep(0)
+            .silent(true)
+            .input_source(ScriptedInput::new(["0", "0", "quit"]))
+            .output_sink(BufferOutput::new())
+            .build();
+
+        let program = Program::builder("counter")
+            .use_defaults()
+            .silent(true)
+            .runnable(Counter { runs: 0, log: log.clone() })
+            .build();
+        system.append_program(program);
+
+        assert!(matches!(system.run(), MenuOutcome::Quit));
+        assert_eq!(*log.lock().unwrap(), vec!["setup", "counter run 1", "counter run 2", "teardown"]);
+    }
+
+    #[test]
+    fn test_quit_at_a_prompt_tears_down() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let buffer = BufferOutput::new();
+        let mut system = System::builder("Test")
+            .use_defaults()
+            .input_source(ScriptedInput::new(["0", "1", "quit"]))
+            .output_sink(buffer.clone())
+            .append_program(Program::builder("counter").use_defaults().silent(true)
+                .runnable(Counter { runs: 0, log: log.clone() }).build())
+            .append_program(Program::builder("ask").use_defaults().silent(true)
+                .action_with_context(|ctx| ctx.input("Name?").map(|_| ())).build())
+            .build();
+
+        assert!(matches!(system.run(), MenuOutcome::Quit));
+        assert_eq!(*log.lock().unwrap(), vec!["setup", "counter run 1", "teardown"]);
+        assert!(buffer.contents().contains("Shutting Down"));
+        assert!(!buffer.contents().contains("Error> Quit"));
+
+        system.set_input_source(ScriptedInput::new(["quit", "quit"]));
+        assert_eq!(system.run_args(["ask"]), 0);
+        assert!(matches!(system.input("More?"), Err(CliError::Quit)));
+    }
+
+    #[test]
+    fn test_stateful_closure() {
+        let total = Arc::new(Mutex::new(0));
+        let total_clone = total.clone();
+        let mut calls = 0;
+
+        let mut program = Program::builder("closure")
+            .use_defaults()
+            .silent(true)
+            .action(move || {
+                calls += 1;
+                *total_clone.lock().unwrap() = calls;
+            })
+            .build();
+
+        assert!(program.run().is_ok());
+        assert!(program.run().is_ok());
+        assert_eq!(*total.lock().unwrap(), 2);
+        // Nothing to tear down for a closure, but it's still safe to call
+        assert!(program.teardown().is_ok());
+    }
+
+    #[test]
+    fn test_argv_dispatch() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let seen_clone = seen.clone();
+
+        let database = System::builder("db")
+            .use_defaults()
+            .append_program(Program::builder("migrate")
+                .use_defaults()
+                .action_with_context(move |ctx| {
+                    seen_clone.lock().unwrap().extend(ctx.args().to_vec());
+                    Ok(())
+                })
+                .build())
+            .build();
+
+        let buffer = BufferOutput::new();
+        let mut system = System::builder("mytool")
+            .use_defaults()
+            .sleep(0)
+            .output_sink(buffer.clone())
+            .append_program(Program::builder("greet").use_defaults().tag("example").action(|| {}).build())
+            .append_program(Program::builder("broken")
+                .use_defaults()
+                .action_with_context(|_| Err(CliError::CommandFailed("nope".to_string())))
+                .build())
+            .append_system(database)
+            .build();
+
+        assert_eq!(system.run_args(["greet"]), 0);
+        assert_eq!(system.run_args(["db", "migrate", "--dry-run"]), 0);
+        assert_eq!(*seen.lock().unwrap(), vec!["--dry-run".to_string()]);
+        assert_eq!(system.run_args(["broken"]), 1);
+        assert_eq!(system.run_args(["missing"]), 2);
+        assert_eq!(system.run_args(["db", "missing"]), 2);
+
+        buffer.clear();
+        assert_eq!(system.run_args(["--help"]), 0);
+        let help = buffer.contents();
+        assert!(help.contains("greet"));
+        assert!(help.contains("Tags: example"));
+    }
+
+    #[test]
+    fn test_argv_falls_back_to_menu() {
+        let mut system = System::builder("mytool")
+            .use_defaults()
+            .sleep(0)
+            .input_source(ScriptedInput::new(["quit"]))
+            .output_sink(BufferOutput::new())
+            .build();
+
+        assert_eq!(system.run_args(Vec::<String>::new()), 0);
+        assert_eq!(system.find_program("greet"), None);
+    }
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_declared_args() {
+        let declared = [
+            Arg::new("name").required(true),
+            Arg::new("count").kind(ArgType::Int).default("1"),
+        ];
+        let flags = [Flag::new("loud").short('l')];
+
+        let parsed = ParsedArgs::parse(&declared, &flags, &strings(&["Alice", "-l"])).unwrap();
+        assert_eq!(parsed.value("name"), Some("Alice"));
+        assert_eq!(parsed.get::<i64>("count"), Some(1));
+        assert!(parsed.flag("loud"));
+
+        let parsed = ParsedArgs::parse(&declared, &flags, &strings(&["--count=3", "Bob"])).unwrap();
+        assert_eq!(parsed.value("name"), Some("Bob"));
+        assert_eq!(parsed.get::<i64>("count"), Some(3));
+        assert!(!parsed.flag("loud"));
+
+        let parsed = ParsedArgs::parse(&declared, &flags, &strings(&["Eve", "--count", "-2"])).unwrap();
+        assert_eq!(parsed.get::<i64>("count"), Some(-2));
+
+        assert!(!ParsedArgs::parse(&declared, &flags, &strings(&["Eve", "--loud=false"])).unwrap().flag("loud"));
+        assert!(ParsedArgs::parse(&declared, &flags, &strings(&["Eve", "--loud=true"])).unwrap().flag("loud"));
+
+        let invalid = |args: &[&str]| ParsedArgs::parse(&declared, &flags, &strings(args));
+        assert!(matches!(invalid(&[]), Err(CliError::InvalidInput(msg)) if msg.contains("<name>")));
+        assert!(matches!(invalid(&["Alice", "many"]), Err(CliError::InvalidInput(msg)) if msg.contains("count")));
+        assert!(matches!(invalid(&["Alice", "--quiet"]), Err(CliError::InvalidInput(msg)) if msg.contains("--quiet")));
+        assert!(matches!(invalid(&["Alice", "--loud=maybe"]), Err(CliError::InvalidInput(msg)) if msg.contains("'maybe'")));
+        assert!(matches!(invalid(&["Alice", "2", "extra"]), Err(CliError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn test_split_args() {
+        assert_eq!(split_args("greet  Alice --loud"), strings(&["greet", "Alice", "--loud"]));
+        assert_eq!(split_args("greet \"Alice Smith\" ''"), strings(&["greet", "Alice Smith", ""]));
+        assert!(split_args("   ").is_empty());
+    }
+
+    fn greeter(greetings: Arc<Mutex<Vec<String>>>) -> Program {
+        Program::builder("greet")
+            .use_defaults()
+            .description("Greets someone")
+            .arg(Arg::new("name").required(true).help("Who to greet"))
+            .flag(Flag::new("loud").short('l').help("Shout the greeting"))
+            .action_with_context(move |ctx| {
+                let mut greeting = format!("Hello {}", ctx.value("name").unwrap());
+                if ctx.flag("loud") {
+                    greeting = greeting.to_uppercase();
+                }
+                greetings.lock().unwrap().push(greeting);
+                Ok(())
+            })
+            .build()
+    }
+
+    #[test]
+    fn test_args_from_menu_prompt_and_argv() {
+        let greetings = Arc::new(Mutex::new(Vec::new()));
+        let buffer = BufferOutput::new();
+        let mut system = System::builder("Test System")
+            .use_defaults()
+            .sleep(0)
+            .output_sink(buffer.clone())
+            .append_program(greeter(greetings.clone()))
+            .build();
+
+        assert!(matches!(system.handle_menu_input("greet Alice --loud"), MenuOutcome::Ran(0, Ok(()))));
+        assert!(matches!(system.handle_menu_input("0 Bob"), MenuOutcome::Ran(0, Ok(()))));
+        assert!(matches!(system.handle_menu_input("greet"), MenuOutcome::Ran(0, Err(CliError::InvalidInput(_)))));
+        assert_eq!(system.run_args(["greet", "Carol"]), 0);
+        assert_eq!(system.run_args(["greet", "--nope"]), 2);
+        assert_eq!(*greetings.lock().unwrap(), vec!["HELLO ALICE", "Hello Bob", "Hello Carol"]);
+
+        buffer.clear();
+        system.show_help();
+        let help = buffer.contents();
+        assert!(help.contains("Usage: greet <name> [-l|--loud]"));
+        assert!(help.contains("Who to greet (string, required)"));
+        assert!(help.contains("Shout the greeting"));
+    }
+
+    #[test]
+    fn test_generated_help() {
+        let buffer = BufferOutput::new();
+        let mut system = System::builder("Test System")
+            .use_defaults()
+            .sleep(0)
+            .output_sink(buffer.clone())
+            .append_program(Program::builder("deploy")
+                .use_defaults()
+                .description("Deploys the app")
+                .long_description("Builds a release and pushes it\nto every server.")
+                .example("deploy staging")
+                .arg(Arg::new("env").required(true))
+                .action(|| {})
+                .build())
+            .append_system(System::builder("db").use_defaults().build())
+            .build();
+
+        assert!(matches!(system.handle_menu_input("help"), MenuOutcome::Help));
+        let overview = buffer.contents();
+        assert!(overview.contains("Deploys the app"));
+        assert!(!overview.contains("to every server."));
+        assert!(overview.contains("db >"));
+        assert!(overview.contains("help [program]"));
+        assert!(overview.contains("quit"));
+
+        buffer.clear();
+        assert!(matches!(system.handle_menu_input("help deploy"), MenuOutcome::Help));
+        let detail = buffer.contents();
+        assert!(detail.contains("to every server."));
+        assert!(detail.contains("Usage: deploy <env>"));
+        assert!(detail.contains("deploy staging"));
+
+        buffer.clear();
+        assert!(matches!(system.handle_menu_input("help db"), MenuOutcome::Help));
+        assert!(buffer.contents().contains("db Help"));
+        assert!(!buffer.contents().contains("Program not found"));
+
+        buffer.clear();
+        assert_eq!(system.run_args(["deploy", "--help"]), 0);
+        assert!(buffer.contents().contains("Examples:"));
+        assert_eq!(system.run_args(["help", "db"]), 0);
+        assert_eq!(system.run_args(["help", "missing"]), 2);
+        buffer.clear();
+        assert_eq!(system.run_args(["db"]), 2);
+        assert!(buffer.contents().contains("db Help"));
+        assert!(buffer.contents().contains("db needs a program to run"));
+        assert_eq!(system.run_args(["--yes"]), 2);
+        assert!(matches!(system.show_program_help("0"), Ok(())));
+    }
+
+    #[test]
+    fn test_custom_meta_command() {
+        let buffer = BufferOutput::new();
+        let mut system = System::builder("Test System")
+            .use_defaults()
+            .sleep(0)
+            .output_sink(buffer.clone())
+            .command(MetaCommand::new("greet", "Greets whoever is named", |sys, args| {
+                if args.is_empty() {
+                    return Err(CliError::InvalidInput("Who?".to_string()));
+                }
+                sys.print(&format!("Hi {} from {}", args.join(" "), sys.name()));
+                Ok(())
+            }).usage("greet <name>"))
+            .build();
+
+        assert!(matches!(system.handle_menu_input("greet Ada"), MenuOutcome::Command(name, Ok(())) if name == "greet"));
+        assert!(buffer.contents().contains("Hi Ada from Test System"));
+        assert!(matches!(system.handle_menu_input("greet"), MenuOutcome::Command(_, Err(CliError::InvalidInput(_)))));
+        // The handler is put back after running, so it can run again
+        assert!(matches!(system.handle_menu_input("greet Bob"), MenuOutcome::Command(_, Ok(()))));
+
+        buffer.clear();
+        system.show_help();
+        assert!(buffer.contents().contains("greet <name>"));
+        assert!(buffer.contents().contains("Greets whoever is named"));
+
+        // A handler that registers a command with its own name replaces itself
+        system.register_command(MetaCommand::new("x", "Old", |sys, _| {
+            sys.register_command(MetaCommand::new("x", "New", |sys, _| {
+                sys.print("new x");
+                Ok(())
+            }));
+            Ok(())
+        }));
+        system.handle_menu_input("x");
+        assert_eq!(system.commands().iter().filter(|c| c.name() == "x").count(), 1);
+        buffer.clear();
+        system.handle_menu_input("x");
+        assert!(buffer.contents().contains("new x"));
+    }
+
+    #[test]
+    fn test_builtin_meta_commands() {
+        let buffer = BufferOutput::new();
+        let mut system = System::builder("Test System")
+            .use_defaults()
+            .sleep(0)
+            .output_sink(buffer.clone())
+            .add_program_with_inheritance("prog".to_string(), || {})
+            .build();
+
+        assert!(system.commands().iter().any(|c| c.name() == "clear" && c.is_builtin()));
+        system.handle_menu_input("settings");
+        assert!(buffer.contents().contains("sleep   0"));
+
+        assert!(matches!(system.handle_menu_input("settings sleep 5"), MenuOutcome::Command(_, Ok(()))));
+        assert!(matches!(system.handle_menu_input("settings color Blue"), MenuOutcome::Command(_, Ok(()))));
+        assert_eq!(system.get_sleep(), 5);
+        assert_eq!(system.programs()[0].get_sleep(), 5);
+        assert!(matches!(system.programs()[0].get_color(), TermColor::Blue));
+        assert!(matches!(system.handle_menu_input("settings sleep soon"), MenuOutcome::Command(_, Err(_))));
+
+        buffer.clear();
+        assert!(matches!(system.handle_menu_input("history"), MenuOutcome::Command(_, Ok(()))));
+        let history = buffer.contents();
+        assert!(history.contains("1     settings"));
+        assert!(history.contains("5     history"));
+        assert_eq!(system.history().len(), 5);
+
+        // Registering a command with a built-in's name replaces it
+        system.register_command(MetaCommand::new("quit", "Not allowed", |_, _| Ok(())));
+        assert!(matches!(system.handle_menu_input("quit"), MenuOutcome::Command(_, Ok(()))));
+    }
+
+    #[test]
+    fn test_select_by_name_prefix_and_alias() {
+        let mut system = System::builder("Test System")
+            .use_defaults()
+            .sleep(0)
+            .output_sink(BufferOutput::new())
+            .append_program(Program::builder("deploy").use_defaults().alias("ship").action(|| {}).build())
+            .append_program(Program::builder("depcheck").use_defaults().action(|| {}).build())
+            .append_program(Program::builder("status").use_defaults().action(|| {}).build())
+            .append_system(System::builder("database").use_defaults().build())
+            .build();
+
+        assert_eq!(system.resolve("deploy"), Selection::Program(0));
+        assert_eq!(system.resolve("ship"), Selection::Program(0));
+        assert_eq!(system.resolve("sh"), Selection::Program(0));
+        assert_eq!(system.resolve("st"), Selection::Program(2));
+        assert_eq!(system.resolve("data"), Selection::System(0));
+        assert_eq!(system.resolve("3"), Selection::System(0));
+        assert_eq!(system.resolve("nothing"), Selection::NotFound);
+        assert_eq!(system.resolve("dep"), Selection::Ambiguous(vec!["deploy".to_string(), "depcheck".to_string()]));
+        assert_eq!(system.resolve("d"),
+            Selection::Ambiguous(vec!["deploy".to_string(), "depcheck".to_string(), "database".to_string()]));
+
+        assert!(matches!(system.handle_menu_input("stat"), MenuOutcome::Ran(2, Ok(()))));
+        assert!(matches!(system.handle_menu_input("dep"), MenuOutcome::Ambiguous(names) if names.len() == 2));
+        assert_eq!(system.run_args(["ship"]), 0);
+    }
+
+    #[test]
+    fn test_one_based_numbering() {
+        let buffer = BufferOutput::new();
+        let mut system = System::builder("Test System")
+            .use_defaults()
+            .sleep(0)
+            .one_based(true)
+            .output_sink(buffer.clone())
+            .input_source(ScriptedInput::new(["quit"]))
+            .append_program(Program::builder("first").use_defaults().action(|| {}).build())
+            .append_system(System::builder("sub").use_defaults().build())
+            .build();
+
+        assert_eq!(system.resolve("0"), Selection::NotFound);
+        assert_eq!(system.resolve("1"), Selection::Program(0));
+        assert_eq!(system.resolve("2"), Selection::System(0));
+
+        system.menu();
+        let menu = console::strip_ansi_codes(&buffer.contents()).to_string();
+        assert!(menu.contains("1)    first"));
+        assert!(menu.contains("2)    sub >"));
+    }
+
+    #[test]
+    fn test_fuzzy_matching() {
+        assert!(fuzzy_score("dpl", "deploy").is_some());
+        assert!(fuzzy_score("xyz", "deploy").is_none());
+        assert!(fuzzy_score("dep", "deploy") > fuzzy_score("dep", "d-e-p"));
+        assert!(fuzzy_score("dep", "deploy") > fuzzy_score("dep", "deployment"));
+        assert_eq!(edit_distance("deplyo", "Deploy"), 2);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(suggestions("deplyo", ["status", "deploy", "depcheck"], 3), vec!["deploy"]);
+    }
+
+    #[test]
+    fn test_menu_search_and_suggestions() {
+        let buffer = BufferOutput::new();
+        let mut system = System::builder("Test System")
+            .use_defaults()
+            .sleep(0)
+            .output_sink(buffer.clone())
+            .append_program(Program::builder("status").use_defaults().description("Shows deployment status").action(|| {}).build())
+            .append_program(Program::builder("deploy").use_defaults().action(|| {}).build())
+            .append_program(Program::builder("depcheck").use_defaults().action(|| {}).build())
+            .append_program(Program::builder("cleanup").use_defaults().tag("maintenance").action(|| {}).build())
+            .build();
+
+        // Name matches rank above the description match
+        assert_eq!(system.search("dep"), vec![1, 2, 0]);
+        assert_eq!(system.search("maint"), vec![3]);
+
+        assert!(matches!(system.handle_menu_input("/dep"), MenuOutcome::Filtered(q) if q == "dep"));
+        system.set_input_source(ScriptedInput::new(["deploy"]));
+        buffer.clear();
+        assert!(matches!(system.menu(), MenuOutcome::Ran(1, Ok(()))));
+        let menu = console::strip_ansi_codes(&buffer.contents()).to_string();
+        assert!(menu.contains("deploy"));
+        assert!(!menu.contains("cleanup"));
+        assert!(menu.contains("/dep > Pick program to launch:"));
+
+        // Running a program clears the search
+        system.set_input_source(ScriptedInput::new(["quit"]));
+        buffer.clear();
+        system.menu();
+        assert!(buffer.contents().contains("cleanup"));
+
+        buffer.clear();
+        assert!(matches!(system.handle_menu_input("deplyo"), MenuOutcome::InvalidInput(_)));
+        assert!(buffer.contents().contains("did you mean: deploy?"));
+        assert_eq!(system.suggest("dpchk"), vec!["depcheck"]);
+        assert!(matches!(system.handle_menu_input("/"), MenuOutcome::Filtered(q) if q.is_empty()));
+    }
+
+    #[test]
+    fn test_picker_keys() {
+        use console::Key;
+        let entry = |label: &str, description: &str| PickerEntry { label: label.to_string(), description: description.to_string() };
+        let mut picker = Picker::new(vec![entry("build", "Compile"), entry("deploy", "Ship it"), entry("cleanup", "Remove files")]);
+
+        assert_eq!(picker.handle_key(Key::Char('j')), PickerAction::Continue);
+        assert_eq!(picker.handle_key(Key::ArrowDown), PickerAction::Continue);
+        assert_eq!(picker.handle_key(Key::ArrowDown), PickerAction::Continue);
+        assert_eq!(picker.handle_key(Key::Enter), PickerAction::Pick(2));
+        picker.handle_key(Key::Char('k'));
+        assert_eq!(picker.highlighted(), Some(1));
+
+        let lines: Vec<String> = picker.render(&console::Style::new()).iter()
+            .map(|l| console::strip_ansi_codes(l).to_string())
+            .collect();
+        assert_eq!(lines[1], "> deploy");
+        assert!(lines.contains(&"Ship it".to_string()));
+
+        // Typing a filter narrows the list, Esc clears it and then cancels
+        picker.handle_key(Key::Char('/'));
+        for c in "cln".chars() {
+            picker.handle_key(Key::Char(c));
+        }
+        assert_eq!(picker.visible(), vec![2]);
+        assert_eq!(picker.handle_key(Key::Char('q')), PickerAction::Continue);
+        picker.handle_key(Key::Backspace);
+        assert_eq!(picker.handle_key(Key::Escape), PickerAction::Continue);
+        assert_eq!(picker.visible(), vec![0, 1, 2]);
+        assert_eq!(picker.handle_key(Key::Escape), PickerAction::Cancel);
+
+        assert_eq!(picker.handle_key(Key::Char('?')), PickerAction::Help);
+        assert_eq!(picker.handle_key(Key::ArrowLeft), PickerAction::Back);
+        assert_eq!(picker.handle_key(Key::Char(':')), PickerAction::Prompt);
+        assert_eq!(picker.handle_key(Key::Char('q')), PickerAction::Quit);
+    }
+
+    #[test]
+    fn test_arrow_mode_falls_back_without_terminal() {
+        let buffer = BufferOutput::new();
+        let mut system = System::builder("Test")
+            .use_defaults()
+            .menu_mode(MenuMode::Arrows)
+            .input_source(ScriptedInput::new(["0"]))
+            .output_sink(buffer.clone())
+            .append_program(Program::builder("noop").use_defaults().action(|| {}).build())
+            .build();
+
+        assert!(matches!(system.menu(), MenuOutcome::Ran(0, Ok(()))));
+        assert!(buffer.contents().contains("Pick program to launch:"));
+    }
+
+    /// A quiet program that does nothing when run, for tests about the menu and picking programs.
+    fn quiet_program(name: &str) -> ProgramBuilder {
+        Program::builder(name).use_defaults().silent(true).action(|| {})
+    }
+
+    fn tagged_program(name: &str, tags: &[&str]) -> ProgramBuilder {
+        quiet_program(name).tags(tags.iter().map(|t| t.to_string()).collect())
+    }
+
+    /// A quiet program that adds its name to `ran` every time it runs, and fails afterwards if `fails` is set.
+    fn recording_program(name: &str, ran: &Arc<Mutex<Vec<String>>>, fails: bool) -> ProgramBuilder {
+        let ran = ran.clone();
+        let label = name.to_string();
+        Program::builder(name)
+            .use_defaults()
+            .silent(true)
+            .action_with_context(move |_| {
+                ran.lock().unwrap().push(label.clone());
+                if fails { Err(CliError::CommandFailed(label.clone())) } else { Ok(()) }
+            })
+    }
+
+    fn batch_system(stop_on_failure: bool, ran: Arc<Mutex<Vec<String>>>) -> System {
+        System::builder("Test")
+            .use_defaults()
+            .one_based(true)
+            .stop_on_failure(stop_on_failure)
+            .output_sink(BufferOutput::new())
+            .append_program(recording_program("build", &ran, false).tag("nightly").build())
+            .append_program(recording_program("test", &ran, true).tag("nightly").build())
+            .append_program(recording_program("lint", &ran, false).tag("quick").build())
+            .append_program(recording_program("deploy", &ran, false).tag("nightly").build())
+            .build()
+    }
+
+    #[test]
+    fn test_select_many() {
+        let system = batch_system(true, Arc::new(Mutex::new(Vec::new())));
+        assert_eq!(system.select_many("1,3").unwrap(), vec![0, 2]);
+        assert_eq!(system.select_many("4, 1-3").unwrap(), vec![3, 0, 1, 2]);
+        assert_eq!(system.select_many("#nightly,lint,1").unwrap(), vec![0, 1, 3, 2]);
+        assert!(matches!(system.select_many("#missing"), Err(CliError::ProgramNotFound(_))));
+        assert!(matches!(system.select_many("2-9"), Err(CliError::ProgramNotFound(_))));
+        assert!(matches!(system.select_many("3-1"), Err(CliError::InvalidInput(_))));
+
+        // Ranges pass over hidden programs and sub-systems
+        let mut system = batch_system(true, Arc::new(Mutex::new(Vec::new())));
+        system.append_program(quiet_program("secret").hidden(true).build());
+        system.append_system(System::builder("Sub").use_defaults().build());
+        assert_eq!(system.select_many("3-6").unwrap(), vec![2, 3]);
+        assert!(matches!(system.select_many("3-7"), Err(CliError::ProgramNotFound(_))));
+        assert!(matches!(system.select_many("6"), Err(CliError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn test_batch_run_and_summary() {
+        let ran = Arc::new(Mutex::new(Vec::new()));
+        let buffer = BufferOutput::new();
+        let mut system = batch_system(true, ran.clone());
+        system.set_output_sink(buffer.clone());
+
+        let results = match system.handle_menu_input("#nightly") {
+            MenuOutcome::Batch(results) => results,
+            other => panic!("expected a batch, got {:?}", other),
+        };
+        assert_eq!(*ran.lock().unwrap(), vec!["build", "test"]);
+        assert!(matches!(results[0].status, BatchStatus::Passed));
+        assert!(results[1].is_failed());
+        assert!(matches!(results[2].status, BatchStatus::Skipped));
+        assert_eq!(results[2].name, "deploy");
+        let summary = console::strip_ansi_codes(&buffer.contents()).to_string();
+        assert!(summary.contains("skipped"));
+        assert!(summary.contains("2 run, 1 failed, 1 skipped"));
+
+        // Without stop-on-failure everything runs
+        ran.lock().unwrap().clear();
+        system.set_stop_on_failure(false);
+        assert!(matches!(system.handle_menu_input("1-4"), MenuOutcome::Batch(r) if r.len() == 4));
+        assert_eq!(ran.lock().unwrap().len(), 4);
+
+        // Spaces outside the commas mean arguments, not a list
+        assert!(matches!(system.handle_menu_input("lint a,b"), MenuOutcome::Ran(2, _)));
+    }
+
+    #[test]
+    fn test_batch_skips_disabled_programs() {
+        let ran = Arc::new(Mutex::new(Vec::new()));
+        let buffer = BufferOutput::new();
+        let mut system = System::builder("Test")
+            .use_defaults()
+            .output_sink(buffer.clone())
+            .append_program(recording_program("offline", &ran, false).disabled_when(|| true, "no network").build())
+            .append_program(recording_program("status", &ran, false).build())
+            .build();
+
+        for results in [system.run_batch(&[0, 1]), system.run_parallel(&[0, 1], 2)] {
+            assert!(matches!(results[0].status, BatchStatus::Skipped));
+            assert!(matches!(results[1].status, BatchStatus::Passed));
+        }
+        assert_eq!(*ran.lock().unwrap(), vec!["status", "status"]);
+        assert!(buffer.contents().contains("offline skipped, disabled: no network"));
+    }
+
+    #[test]
+    fn test_batch_out_of_bounds() {
+        let mut system = System::builder("Test").use_defaults().output_sink(BufferOutput::new()).build();
+        for results in [system.run_batch(&[5]), system.run_parallel(&[5], 2)] {
+            assert_eq!(results.len(), 1);
+            assert!(matches!(&results[0].status, BatchStatus::Failed(CliError::ProgramNotFound(_))));
+        }
+
+        let ran = Arc::new(Mutex::new(Vec::new()));
+        let mut system = batch_system(false, ran.clone());
+        let results = system.run_batch(&[0, 9, 2]);
+        assert!(results[1].is_failed());
+        assert_eq!(*ran.lock().unwrap(), vec!["build", "lint"]);
+    }
+
+    #[test]
+    fn test_run_parallel() {
+        use std::sync::atomic::AtomicUsize;
+        use std::time::Duration;
+        let running = Arc::new(AtomicUsize::new(0));
+        let most = Arc::new(AtomicUsize::new(0));
+        let buffer = BufferOutput::new();
+        let mut system = System::builder("Test").use_defaults().output_sink(buffer.clone()).build();
+        for name in ["db", "cache", "queue", "disk"] {
+            let (running, most) = (running.clone(), most.clone());
+            system.append_program(Program::builder(name)
+                .use_defaults()
+                .silent(true)
+                .tag("health")
+                .action_with_context(move |ctx| {
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    most.fetch_max(now, Ordering::SeqCst);
+                    ctx.print("checking");
+                    std::thread::sleep(Duration::from_millis(50));
+                    ctx.print("done");
+                    running.fetch_sub(1, Ordering::SeqCst);
+                    if ctx.name() == "disk" { Err(CliError::CommandFailed("disk full".to_string())) } else { Ok(()) }
+                })
+                .build());
+        }
+
+        let results = system.run_parallel(&[3, 0, 1, 2], 2);
+        assert_eq!(most.load(Ordering::SeqCst), 2);
+        assert_eq!(results.iter().map(|r| r.index).collect::<Vec<_>>(), vec![3, 0, 1, 2]);
+        assert!(results[0].is_failed());
+
+        // Each program's lines come out together under its header
+        let lines: Vec<String> = buffer.lines().iter().map(|l| console::strip_ansi_codes(l).to_string()).collect();
+        let header = lines.iter().position(|l| l.starts_with("── db (ok")).unwrap();
+        assert!(lines[header + 1].contains("db> checking"));
+        assert!(lines[header + 2].contains("db> done"));
+        let failed = lines.iter().position(|l| l.starts_with("── disk (failed")).unwrap();
+        assert!(lines[failed + 3].contains("disk full"));
+        assert_eq!(lines.last().unwrap(), "4 run, 1 failed, 0 skipped");
+
+        // Programs get the system's output back afterwards
+        buffer.clear();
+        most.store(0, Ordering::SeqCst);
+        assert!(matches!(system.handle_menu_input("&#health"), MenuOutcome::Batch(r) if r.len() == 4));
+        assert!(most.load(Ordering::SeqCst) >= 1);
+        buffer.clear();
+        assert!(system.run_program(0).is_ok());
+        assert!(buffer.contents().contains("db> checking"));
+    }
+
+    #[test]
+    fn test_parallel_prompts_show_before_reading() {
+        // Answers from a script, keeping what was on screen at the moment of each read
+        struct Watching(BufferOutput, Vec<&'static str>, Arc<Mutex<Vec<String>>>);
+        impl crate::InputSource for Watching {
+            fn read_line(&mut self) -> Result<String, CliError> {
+                self.2.lock().unwrap().push(self.0.contents());
+                Ok(self.1.remove(0).to_string())
+            }
+        }
+        let watched = |answers: Vec<&'static str>| {
+            let buffer = BufferOutput::new();
+            let screens = Arc::new(Mutex::new(Vec::new()));
+            let system = System::builder("Test")
+                .use_defaults()
+                .input_source(Watching(buffer.clone(), answers, screens.clone()))
+                .output_sink(buffer.clone())
+                .build();
+            (system, buffer, screens)
+        };
+
+        let (mut system, buffer, screens) = watched(vec!["Ada", "Bob"]);
+        for name in ["first", "second"] {
+            system.append_program(Program::builder(name)
+                .use_defaults()
+                .silent(true)
+                .action_with_context(|ctx| {
+                    let answer = ctx.input("What is your name?")?;
+                    ctx.print(&format!("Hello {}", answer));
+                    Ok(())
+                })
+                .build());
+        }
+        let results = system.run_parallel(&[0, 1], 2);
+        assert!(results.iter().all(|r| matches!(r.status, BatchStatus::Passed)));
+        // Every read has its own question on screen, not just the first one's
+        for (read, screen) in screens.lock().unwrap().iter().enumerate() {
+            assert_eq!(screen.matches("What is your name?").count(), read + 1);
+        }
+        let lines: Vec<String> = buffer.lines().iter().map(|l| console::strip_ansi_codes(l).to_string()).collect();
+        let header = lines.iter().position(|l| l.starts_with("── first (ok")).unwrap();
+        assert!(lines[header + 1].starts_with("  first> Hello "));
+
+        // The options and the reason a wrong answer was turned down show up before the next read too
+        let (mut system, _, screens) = watched(vec!["staging?", "prod"]);
+        system.append_program(Program::builder("deploy")
+            .use_defaults()
+            .silent(true)
+            .action_with_context(|ctx| ctx.select("Env?", &["staging", "prod"]).map(|_| ()))
+            .build());
+        assert!(matches!(system.run_parallel(&[0], 2)[0].status, BatchStatus::Passed));
+        let screens = screens.lock().unwrap();
+        assert!(screens[0].contains("1) staging") && screens[0].contains("2) prod"));
+        assert!(screens[1].contains("'staging?' is not one of the options"));
+    }
+
+    #[test]
+    fn test_tag_expressions() {
+        let tags = |list: &[&str]| list.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+        let expr = TagExpr::parse("(prod | staging) & readonly").unwrap();
+        assert!(expr.matches(&tags(&["staging", "readonly"])));
+        assert!(!expr.matches(&tags(&["prod"])));
+        assert!(!expr.matches(&tags(&["dev", "readonly"])));
+
+        // ! binds tighter than &, which binds tighter than |
+        assert_eq!(TagExpr::parse("a | b & !c").unwrap().to_string(), "(a | (b & !c))");
+        assert!(TagExpr::parse("db & !slow").unwrap().matches(&tags(&["db"])));
+        assert!(!TagExpr::parse("db & !slow").unwrap().matches(&tags(&["db", "slow"])));
+
+        for bad in ["", "db &", "(net | dns", "db slow", "| dns", "db & )"] {
+            assert!(matches!(TagExpr::parse(bad), Err(CliError::InvalidInput(_))), "{:?} should not parse", bad);
+        }
+    }
+
+    #[test]
+    fn test_tag_queries_in_system() {
+        let buffer = BufferOutput::new();
+        let mut system = System::builder("Test")
+            .use_defaults()
+            .output_sink(buffer.clone())
+            .append_program(tagged_program("migrate", &["db", "slow"]).build())
+            .append_program(tagged_program("backup", &["db"]).build())
+            .append_program(tagged_program("ping", &["net"]).build())
+            .append_program(tagged_program("resolve", &["dns"]).build())
+            .build();
+
+        let names = |programs: Vec<&Program>| programs.iter().map(|p| p.get_name()).collect::<Vec<_>>();
+        assert_eq!(names(system.programs_with_tag("db").unwrap()), vec!["migrate", "backup"]);
+        assert_eq!(names(system.programs_with_tag("db & !slow").unwrap()), vec!["backup"]);
+        assert_eq!(names(system.programs_with_tag("net | dns").unwrap()), vec!["ping", "resolve"]);
+        assert!(matches!(system.programs_with_tag("net |"), Err(CliError::InvalidInput(_))));
+        assert!(matches!(system.menu_with_tags_filter("(db"), Err(CliError::InvalidInput(_))));
+
+        assert_eq!(system.select_many("#net | dns, 0").unwrap(), vec![2, 3, 0]);
+        assert!(matches!(system.handle_menu_input("#db & !slow"), MenuOutcome::Batch(r) if r.len() == 1));
+
+        assert!(matches!(system.handle_menu_input("/#db & !(slow)"), MenuOutcome::Filtered(_)));
+        system.set_input_source(ScriptedInput::new(["quit"]));
+        buffer.clear();
+        system.menu();
+        assert!(buffer.contents().contains("backup"));
+        assert!(!buffer.contents().contains("migrate"));
+
+        buffer.clear();
+        assert!(matches!(system.handle_menu_input("/#db &"), MenuOutcome::InvalidInput(_)));
+        assert!(buffer.contents().contains("expected a tag"));
+    }
+
+    #[test]
+    fn test_hierarchical_tags() {
+        let buffer = BufferOutput::new();
+        let mut system = System::builder("Test")
+            .use_defaults()
+            .group_by(MenuGrouping::Tag)
+            .output_sink(buffer.clone())
+            .append_program(tagged_program("dig", &["net/dns"]).build())
+            .append_program(tagged_program("curl", &["net/http", "tools"]).build())
+            .append_program(tagged_program("df", &["disk"]).build())
+            .append_program(tagged_program("misc", &[]).build())
+            .append_program(tagged_program("network", &["network"]).build())
+            .build();
+
+        assert!(system.programs()[0].has_tag("net"));
+        assert!(!system.programs()[4].has_tag("net"));
+        let names = |programs: Vec<&Program>| programs.iter().map(|p| p.get_name()).collect::<Vec<_>>();
+        assert_eq!(names(system.programs_with_tag("net & !net/http").unwrap()), vec!["dig"]);
+
+        let tree = system.tag_tree();
+        assert_eq!(tree.iter().map(|n| n.name.as_str()).collect::<Vec<_>>(), vec!["disk", "net", "network", "tools"]);
+        assert_eq!(tree[1].children.iter().map(|n| n.path.as_str()).collect::<Vec<_>>(), vec!["net/dns", "net/http"]);
+
+        system.set_input_source(ScriptedInput::new(["quit"]));
+        system.menu();
+        let lines: Vec<String> = buffer.lines().iter().map(|l| console::strip_ansi_codes(l).to_string()).collect();
+        let heading = |name: &str| lines.iter().position(|l| l == &format!("[{}]", name)).unwrap();
+        assert!(heading("disk") < heading("net") && heading("net") < heading("tools") && heading("tools") < heading("untagged"));
+        assert!(lines[heading("net") + 1].contains("dig"));
+        assert!(lines[heading("tools") + 1].contains("curl"));
+        assert!(lines[heading("untagged") + 1].contains("misc"));
+    }
+
+    #[test]
+    fn test_menu_order_and_categories() {
+        let buffer = BufferOutput::new();
+        let mut system = System::builder("Test")
+            .use_defaults()
+            .order_by(MenuOrder::Category)
+            .output_sink(buffer.clone())
+            .append_program(quiet_program("zip").category("files").build())
+            .append_program(quiet_program("backup").category("files").priority(5).build())
+            .append_program(quiet_program("ping").category("network").build())
+            .append_program(quiet_program("about").build())
+            .build();
+
+        let listed = |system: &mut System| {
+            buffer.clear();
+            system.set_input_source(ScriptedInput::new(["quit"]));
+            system.menu();
+            buffer.lines().iter()
+                .map(|l| console::strip_ansi_codes(l).to_string())
+                .filter(|l| !l.ends_with("Pick program to launch:") && !l.contains("> quit"))
+                .map(|l| l.split_whitespace().take(2).collect::<Vec<_>>().join(" "))
+                .collect::<Vec<_>>()
+        };
+
+        // Numbers stay the same whatever the order
+        assert_eq!(listed(&mut system), vec!["[files]", "1) backup", "0) zip", "[network]", "2) ping", "[other]", "3) about"]);
+        system.set_menu_order(MenuOrder::Priority);
+        assert_eq!(listed(&mut system), vec!["1) backup", "0) zip", "2) ping", "3) about"]);
+        system.set_menu_order(MenuOrder::Name);
+        assert_eq!(listed(&mut system), vec!["3) about", "1) backup", "2) ping", "0) zip"]);
+
+        system.set_menu_order(MenuOrder::RecentlyUsed);
+        assert!(system.run_program(2).is_ok());
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        assert!(system.run_program(3).is_ok());
+        assert_eq!(listed(&mut system), vec!["3) about", "2) ping", "0) zip", "1) backup"]);
+    }
+
+    #[test]
+    fn test_hidden_disabled_and_visible_when() {
+        let connected = Arc::new(AtomicBool::new(false));
+        let online = connected.clone();
+        let configured = Arc::new(AtomicBool::new(false));
+        let has_config = configured.clone();
+        let buffer = BufferOutput::new();
+        let mut system = System::builder("Test")
+            .use_defaults()
+            .output_sink(buffer.clone())
+            .append_program(Program::builder("status").use_defaults().silent(true).action(|| {}).build())
+            .append_program(Program::builder("reindex").use_defaults().silent(true).hidden(true).tag("db").action(|| {}).build())
+            .append_program(Program::builder("deploy")
+                .use_defaults()
+                .silent(true)
+                .disabled_when(move || !has_config.load(Ordering::SeqCst), "requires $KUBECONFIG")
+                .action(|| {})
+                .build())
+            .append_program(Program::builder("sync")
+                .use_defaults()
+                .silent(true)
+                .tag("db")
+                .visible_when(move || online.load(Ordering::SeqCst))
+                .action(|| {})
+                .build())
+            .build();
+
+        let menu = |system: &mut System| {
+            buffer.clear();
+            system.set_input_source(ScriptedInput::new(["quit"]));
+            system.menu();
+            console::strip_ansi_codes(&buffer.contents()).to_string()
+        };
+        let shown = menu(&mut system);
+        assert!(shown.contains("status"));
+        assert!(!shown.contains("reindex"));
+        assert!(!shown.contains("sync"));
+        assert!(shown.contains("disabled: requires $KUBECONFIG"));
+
+        // visible_when is checked on every render
+        connected.store(true, Ordering::SeqCst);
+        assert!(menu(&mut system).contains("sync"));
+
+        // Hidden programs run by exact name only
+        assert_eq!(system.resolve("reindex"), Selection::Program(1));
+        assert_eq!(system.resolve("1"), Selection::NotFound);
+        assert_eq!(system.resolve("rei"), Selection::NotFound);
+        assert_eq!(system.select_many("#db").unwrap(), vec![3]);
+        assert_eq!(system.select_many("0-3").unwrap(), vec![0, 2, 3]);
+
+        assert!(matches!(system.run_program(2), Err(CliError::ProgramDisabled(reason)) if reason.contains("requires $KUBECONFIG")));
+        configured.store(true, Ordering::SeqCst);
+        assert!(system.run_program(2).is_ok());
+        assert!(!menu(&mut system).contains("disabled"));
+    }
+
+    #[test]
+    fn test_requirements() {
+        assert!(Requirement::File("Cargo.toml".into()).is_met());
+        assert!(!Requirement::File("no/such/file".into()).is_met());
+        assert!(!Requirement::Binary("surely-not-an-installed-tool".to_string()).is_met());
+        #[cfg(unix)]
+        assert!(Requirement::Binary("sh".to_string()).is_met());
+        assert!(!Requirement::Env("CLI_TOOLBOX_TEST_UNSET".to_string()).is_met());
+        assert_eq!(Requirement::Env("TOKEN".to_string()).to_string(), "$TOKEN");
+
+        let program = Program::builder("deploy")
+            .use_defaults()
+            .requires_file("Cargo.toml")
+            .requires_env("CLI_TOOLBOX_TEST_TOKEN")
+            .action(|| {})
+            .build();
+        assert_eq!(program.disabled_reason().as_deref(), Some("requires $CLI_TOOLBOX_TEST_TOKEN"));
+        std::env::set_var("CLI_TOOLBOX_TEST_TOKEN", "secret");
+        assert_eq!(program.disabled_reason(), None);
+    }
+
+    #[test]
+    fn test_doctor_command() {
+        let buffer = BufferOutput::new();
+        let mut system = System::builder("Test")
+            .use_defaults()
+            .output_sink(buffer.clone())
+            .append_program(Program::builder("status").use_defaults().action(|| {}).build())
+            .append_program(Program::builder("query")
+                .use_defaults()
+                .requires_file("Cargo.toml")
+                .requires_binary("surely-not-an-installed-tool")
+                .action(|| {})
+                .build())
+            .append_system(System::builder("ops")
+                .use_defaults()
+                .append_program(Program::builder("backup").use_defaults().requires_file("src").action(|| {}).build())
+                .build())
+            .build();
+
+        assert!(matches!(system.run_program(1), Err(CliError::ProgramDisabled(reason)) if reason.contains("surely-not-an-installed-tool")));
+        assert!(matches!(system.handle_menu_input("doctor"),
+            MenuOutcome::Command(name, Err(CliError::CommandFailed(_))) if name == "doctor"));
+        let lines: Vec<String> = buffer.lines().iter()
+            .map(|l| console::strip_ansi_codes(l).split_whitespace().collect::<Vec<_>>().join(" "))
+            .collect();
+        assert!(lines.contains(&"status - none".to_string()));
+        assert!(lines.contains(&"query Cargo.toml ok".to_string()));
+        assert!(lines.contains(&"query surely-not-an-installed-tool missing".to_string()));
+        assert!(lines.contains(&"ops/backup src ok".to_string()));
+        assert!(lines.iter().any(|l| l.contains("1 requirement(s) missing")));
+    }
+
+    fn dangerous_system(style: ConfirmStyle, ran: Arc<Mutex<Vec<String>>>) -> System {
+        System::builder("Test")
+            .use_defaults()
+            .confirm_tag("dangerous")
+            .confirm_style(style)
+            .output_sink(BufferOutput::new())
+            .append_program(recording_program("drop-db", &ran, false).confirm("This drops the staging DB").build())
+            .append_program(recording_program("wipe", &ran, false).tag("dangerous/data").build())
+            .append_program(recording_program("status", &ran, false).build())
+            .build()
+    }
+
+    #[test]
+    fn test_confirmation_prompts() {
+        let ran = Arc::new(Mutex::new(Vec::new()));
+        let buffer = BufferOutput::new();
+        let mut system = dangerous_system(ConfirmStyle::YesNo, ran.clone());
+        system.set_output_sink(buffer.clone());
+
+        system.set_input_source(ScriptedInput::new(["n", "Y", "", "yes"]));
+        assert!(matches!(system.handle_menu_input("drop-db"), MenuOutcome::Ran(0, Err(CliError::NotConfirmed(_)))));
+        assert!(buffer.contents().contains("Warning: This drops the staging DB"));
+        assert!(system.run_program(0).is_ok());
+        assert!(matches!(system.run_program(1), Err(CliError::NotConfirmed(_))));
+        assert!(buffer.contents().contains("wipe is tagged dangerous"));
+        assert!(system.run_program(1).is_ok());
+        assert!(system.run_program(2).is_ok());
+        assert_eq!(*ran.lock().unwrap(), vec!["drop-db", "wipe", "status"]);
+
+        // Declined programs in a batch are skipped
+        ran.lock().unwrap().clear();
+        system.set_input_source(ScriptedInput::new(["no"]));
+        let results = system.run_batch(&[1, 2]);
+        assert!(matches!(results[0].status, BatchStatus::Skipped));
+        assert!(matches!(results[1].status, BatchStatus::Passed));
+        assert_eq!(*ran.lock().unwrap(), vec!["status"]);
+    }
+
+    #[test]
+    fn test_bench_confirms_and_skips_disabled() {
+        let ran = Arc::new(Mutex::new(Vec::new()));
+        let buffer = BufferOutput::new();
+        let mut system = dangerous_system(ConfirmStyle::YesNo, ran.clone());
+        system.append_program(quiet_program("offline").disabled_when(|| true, "no network").build());
+        system.set_output_sink(buffer.clone());
+        system.set_input_source(ScriptedInput::new(["n", "y"]));
+
+        system.run_bench();
+        assert_eq!(*ran.lock().unwrap(), vec!["wipe", "status"]);
+        assert!(buffer.contents().contains("drop-db ... skipped, not confirmed"));
+        assert!(buffer.contents().contains("offline ... skipped, disabled: no network"));
+        assert!(buffer.contents().contains("status ... bench:"));
+    }
+
+    #[test]
+    fn test_confirmation_by_name_and_yes_flag() {
+        let ran = Arc::new(Mutex::new(Vec::new()));
+        let mut system = dangerous_system(ConfirmStyle::TypeName, ran.clone());
+
+        system.set_input_source(ScriptedInput::new(["y", "wipe"]));
+        assert!(matches!(system.run_program(1), Err(CliError::NotConfirmed(_))));
+        assert!(system.run_program(1).is_ok());
+
+        // argv mode asks too unless --yes is given
+        system.set_input_source(ScriptedInput::new(Vec::<String>::new()));
+        assert_eq!(system.run_args(["drop-db"]), 1);
+        assert_eq!(system.run_args(["drop-db", "--yes"]), 0);
+        assert_eq!(system.run_args(["--yes", "drop-db"]), 0);
+        assert_eq!(*ran.lock().unwrap(), vec!["wipe", "drop-db", "drop-db"]);
+
+        // Past the program's name, or after `--`, it belongs to the program
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let program = |name: &str, seen: Arc<Mutex<Vec<String>>>| Program::builder(name)
+            .use_defaults()
+            .silent(true)
+            .confirm("Really?")
+            .action_with_context(move |ctx| {
+                seen.lock().unwrap().push(ctx.args().join(" "));
+                Ok(())
+            });
+        system.append_program(program("echo", seen.clone()).build());
+        system.append_program(program("ask", seen.clone()).flag(Flag::new("yes")).build());
+        assert_eq!(system.run_args(["echo", "--yes", "--", "--yes"]), 0);
+        assert_eq!(system.run_args(["echo", "-v", "--yes"]), 1);
+        assert_eq!(system.run_args(["--yes", "ask", "--yes"]), 0);
+        assert_eq!(system.run_args(["ask", "--yes"]), 1);
+        assert_eq!(*seen.lock().unwrap(), vec!["-- --yes", "--yes"]);
+    }
+
+    #[test]
+    fn test_input_parsed() {
+        let buffer = BufferOutput::new();
+        let system = System::builder("Test")
+            .use_defaults()
+            .input_source(ScriptedInput::new(["abc", "8080", "", "0", "70000", "443", "x", "y", "z"]))
+            .output_sink(buffer.clone())
+            .build();
+
+        assert_eq!(system.input_parsed::<u16>("Port").unwrap(), 8080);
+        assert!(buffer.contents().contains("'abc' is not valid, invalid digit found in string"));
+
+        let port = || Prompt::new("Port").default(8080u16).range(1..=65535);
+        assert_eq!(port().full_label(), "Port (1-65535) [8080]");
+        assert_eq!(system.prompt(port()).unwrap(), 8080);
+        assert_eq!(system.prompt(port()).unwrap(), 443);
+        assert!(buffer.contents().contains("0 is not between 1 and 65535"));
+        assert!(buffer.contents().contains("'70000' is not valid, number too large"));
+
+        let result = system.prompt(Prompt::new("Port").default(0u16).range(1..=65535));
+        assert!(matches!(result, Err(CliError::InvalidInput(reason)) if reason.contains("0 is not between 1 and 65535")));
+
+        let result = system.prompt(Prompt::<f64>::new("Ratio").retries(2));
+        assert!(matches!(result, Err(CliError::InvalidInput(reason)) if reason.contains("'z'")));
+        assert!(matches!(system.input_parsed::<i32>("More"), Err(CliError::EndOfInput)));
+    }
+
+    #[test]
+    fn test_choice_prompts() {
+        let buffer = BufferOutput::new();
+        let system = System::builder("Test")
+            .use_defaults()
+            .input_source(ScriptedInput::new(["maybe", "", "YES", "4", "gr", "Blue", "1,x", "3, 1-2, red", ""]))
+            .output_sink(buffer.clone())
+            .build();
+        let colors = ["Red", "Green", "Blue"];
+
+        assert!(system.confirm("Continue?", true).unwrap());
+        assert!(buffer.contents().contains("Continue? [Y/n]"));
+        assert!(buffer.contents().contains("'maybe' is not yes or no"));
+        assert!(system.confirm("Really?", false).unwrap());
+
+        assert_eq!(system.select("Pick a color", &colors).unwrap(), 1);
+        assert!(buffer.contents().contains("1) Red"));
+        assert!(buffer.contents().contains("4 is not between 1 and 3"));
+        assert_eq!(system.select("Pick a color", &colors).unwrap(), 2);
+
+        assert_eq!(system.multi_select("Pick colors", &colors).unwrap(), vec![2, 0, 1]);
+        assert!(buffer.contents().contains("'x' is not one of the options"));
+        assert_eq!(system.multi_select("Pick colors", &colors).unwrap(), Vec::<usize>::new());
+        assert!(matches!(system.select("Pick", &Vec::<String>::new()), Err(CliError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn test_secret_input() {
+        let buffer = BufferOutput::new();
+        let mut system = System::builder("Test")
+            .use_defaults()
+            .input_source(ScriptedInput::new(["hunter2", "login hunter2"]))
+            .output_sink(buffer.clone())
+            .build();
+
+        let token = system.input_secret("Token").unwrap();
+        assert_eq!(token.expose(), "hunter2");
+        assert_eq!(token.to_string(), "********");
+        assert_eq!(format!("{:?}", token), "Secret(********)");
+        assert!(buffer.contents().contains("Test> ********"));
+
+        assert_eq!(system.input("Command").unwrap(), "login hunter2");
+        assert!(buffer.contents().contains("Test> login ********"));
+        assert!(!buffer.contents().contains("hunter2"));
+
+        // Only whole tokens are replaced
+        system.print("hunter2s and hunter2, hunter2.");
+        assert!(buffer.contents().contains("Test> hunter2s and ********, ********."));
+    }
+
+    #[test]
+    fn test_short_secrets_leave_output_alone() {
+        let help = |secret: &str| {
+            let buffer = BufferOutput::new();
+            let system = System::builder("Test")
+                .use_defaults()
+                .input_source(ScriptedInput::new([secret]))
+                .output_sink(buffer.clone())
+                .build();
+            system.input_secret("Token").unwrap();
+            buffer.clear();
+            system.show_help();
+            buffer.contents()
+        };
+        let clean = help("a much longer token");
+        assert!(clean.contains("Test Help"));
+        assert_eq!(help("e"), clean);
+
+        // Escape sequences are never rewritten, even when the secret appears inside one
+        let buffer = BufferOutput::new();
+        let output = crate::cli::output::shared_output(buffer.clone());
+        output.lock().unwrap().redact("38;5");
+        output.lock().unwrap().write_line("\x1b[38;5;208mcolour 38;5\x1b[0m");
+        assert_eq!(buffer.contents(), "\x1b[38;5;208mcolour ********\x1b[0m");
+    }
+
+    #[test]
+    fn test_validators() {
+        use validate::*;
+
+        assert_eq!(non_empty().validate("  "), Err("Can't be empty".to_string()));
+        assert_eq!(length(2..=4).validate("hello"), Err("5 characters is not between 2 and 4".to_string()));
+        assert_eq!(number(1..=10u8).validate("11"), Err("11 is not between 1 and 10".to_string()));
+        assert!(number(1..=10u8).validate("ten").unwrap_err().starts_with("'ten' is not valid"));
+        assert_eq!(one_of(["dev", "prod"]).validate("test"), Err("'test' is not one of dev, prod".to_string()));
+        assert!(regex("^[a-z]+$").unwrap().validate("abc").is_ok());
+        assert_eq!(regex("^[a-z]+$").unwrap().validate("a1").unwrap_err(), "'a1' doesn't match ^[a-z]+$");
+        assert!(matches!(regex("(").map(|_| ()), Err(CliError::InvalidInput(_))));
+        assert!(path_exists().validate("src").is_ok() && is_dir().validate("src").is_ok());
+        assert!(is_dir().validate("Cargo.toml").is_err());
+
+        assert!(email().validate("me@example.com").is_ok());
+        assert!(["me@localhost", "@example.com", "me@ex ample.com", "a@b@c.d"].iter().all(|e| email().validate(e).is_err()));
+        assert!(url().validate("https://example.com/path?q=1").is_ok());
+        assert!(["example.com", "https://", "1http://example.com"].iter().all(|u| url().validate(u).is_err()));
+
+        let port = non_empty().and(number(1..=65535u16)).or(one_of(["auto"]));
+        assert!(port.validate("auto").is_ok() && port.validate("8080").is_ok());
+        assert_eq!(port.validate("").unwrap_err(), "'' is not one of auto");
+        assert_eq!(length(1..=3).message("Too long").validate("abcd").unwrap_err(), "Too long");
+        assert_eq!((|s: &str| s == "ok").validate("no").unwrap_err(), "Invalid input");
+        assert_eq!(custom(|_| Err("nope".to_string())).validate("x").unwrap_err(), "nope");
+    }
+
+    #[test]
+    fn test_input_with_validator() {
+        let buffer = BufferOutput::new();
+        let system = System::builder("Test")
+            .use_defaults()
+            .input_source(ScriptedInput::new(["", "abc", "42"]))
+            .output_sink(buffer.clone())
+            .build();
+
+        let answer = system.input_with_validation("Answer", validate::non_empty().and(validate::number(0..=100)));
+        assert_eq!(answer.unwrap(), "42");
+        assert!(buffer.contents().contains("Can't be empty"));
+        assert!(buffer.contents().contains("'abc' is not valid, invalid digit found in string"));
+    }
 }
 
 // Integration tests will be added in a separate module when we have proper mocking